@@ -0,0 +1,272 @@
+use anyhow::{format_err, Context, Result};
+use std::io::Read;
+
+/// Package archive files by path, relative to the package root.
+pub type Files = std::collections::BTreeMap<std::path::PathBuf, Vec<u8>>;
+
+/// Read the files of a gzip compressed package archive (tarball).
+///
+/// npm archives hold the package within a single top level directory, usually `package/`,
+/// which is stripped from file paths. Archives which contain links, special files,
+/// absolute paths or parent directory components are rejected.
+pub fn read_files(content: &[u8]) -> Result<Files> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(content));
+    let mut files = Files::new();
+    for entry in archive
+        .entries()
+        .context("Failed to read package archive.")?
+    {
+        let mut entry = entry.context("Failed to read package archive entry.")?;
+        let raw_path = entry.path()?.to_path_buf();
+
+        match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {}
+            tar::EntryType::Directory | tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => {
+                continue
+            }
+            tar::EntryType::Symlink | tar::EntryType::Link => {
+                return Err(format_err!(
+                    "Package archive contains a link: {}",
+                    raw_path.display()
+                ))
+            }
+            entry_type => {
+                return Err(format_err!(
+                    "Package archive contains unsupported entry type {:?}: {}",
+                    entry_type,
+                    raw_path.display()
+                ))
+            }
+        }
+
+        let path = match get_package_path(&raw_path)? {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut file_content = Vec::new();
+        entry.read_to_end(&mut file_content).context(format!(
+            "Failed to read package file: {}",
+            raw_path.display()
+        ))?;
+        // Later entries replace earlier entries with the same path, as with npm.
+        files.insert(path, file_content);
+    }
+    Ok(files)
+}
+
+/// Returns an archive entry path relative to the package root.
+///
+/// Returns `None` for the top level directory itself.
+fn get_package_path(path: &std::path::Path) -> Result<Option<std::path::PathBuf>> {
+    let mut package_path = std::path::PathBuf::new();
+    let mut is_top_level_directory = true;
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(name) => {
+                if is_top_level_directory {
+                    is_top_level_directory = false;
+                } else {
+                    package_path.push(name);
+                }
+            }
+            std::path::Component::CurDir => {}
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => {
+                return Err(format_err!(
+                    "Package archive contains an absolute path: {}",
+                    path.display()
+                ))
+            }
+            std::path::Component::ParentDir => {
+                return Err(format_err!(
+                    "Package archive contains a path outside of the package: {}",
+                    path.display()
+                ))
+            }
+        }
+    }
+    if package_path.as_os_str().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(package_path))
+}
+
+/// Extract a gzip compressed package archive (tarball) into a new directory.
+///
+/// The destination must not exist. Files are written to a temporary directory beside the
+/// destination first, so that a rejected archive leaves nothing behind.
+pub fn extract(content: &[u8], destination: &std::path::Path) -> Result<()> {
+    if destination.exists() {
+        return Err(format_err!(
+            "Extraction destination already exists: {}",
+            destination.display()
+        ));
+    }
+    let files = read_files(content)?;
+
+    let parent_directory = destination
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or_else(|| std::path::Path::new("."));
+    std::fs::create_dir_all(parent_directory)?;
+    let tmp_dir = tempdir::TempDir::new_in(parent_directory, "vouch_js_extract")?;
+    for (path, file_content) in &files {
+        let file_path = tmp_dir.path().join(path);
+        if let Some(directory) = file_path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&file_path, file_content)
+            .context(format!("Failed to write package file: {}", path.display()))?;
+    }
+    let tmp_path = tmp_dir.into_path();
+    if let Err(error) = std::fs::rename(&tmp_path, destination) {
+        std::fs::remove_dir_all(&tmp_path).ok();
+        return Err(error).context(format!(
+            "Failed to create extraction destination: {}",
+            destination.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Archive entry given its path, type, link target and content.
+    struct Entry<'a> {
+        path: &'a str,
+        entry_type: tar::EntryType,
+        link_name: Option<&'a str>,
+        content: &'a str,
+    }
+
+    fn get_file<'a>(path: &'a str, content: &'a str) -> Entry<'a> {
+        Entry {
+            path,
+            entry_type: tar::EntryType::Regular,
+            link_name: None,
+            content,
+        }
+    }
+
+    /// Returns a gzip compressed archive. Entry paths are written verbatim, so that
+    /// unsafe paths can be tested.
+    fn get_archive(entries: &[Entry]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for entry in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+            header.set_entry_type(entry.entry_type);
+            if let Some(link_name) = entry.link_name {
+                header.as_old_mut().linkname[..link_name.len()]
+                    .copy_from_slice(link_name.as_bytes());
+            }
+            header.set_size(entry.content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, entry.content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_files_strips_top_level_directory() -> Result<()> {
+        let content = get_archive(&[
+            Entry {
+                path: "package/",
+                entry_type: tar::EntryType::Directory,
+                link_name: None,
+                content: "",
+            },
+            get_file("package/package.json", "{}"),
+            get_file("package/lib/index.js", "module.exports = 1;"),
+            get_file("./package/README.md", "# d3"),
+        ]);
+        let files = read_files(&content)?;
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                std::path::Path::new("README.md"),
+                std::path::Path::new("lib/index.js"),
+                std::path::Path::new("package.json"),
+            ]
+        );
+        assert_eq!(files[std::path::Path::new("package.json")], b"{}");
+
+        // Some archives use a top level directory other than `package/`.
+        let content = get_archive(&[get_file("node/index.js", "")]);
+        assert!(read_files(&content)?.contains_key(std::path::Path::new("index.js")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_rejects_unsafe_entries() -> Result<()> {
+        let unsafe_entries = vec![
+            (get_file("package/../x", "x"), "outside of the package"),
+            (get_file("/etc/x", "x"), "absolute path"),
+            (
+                Entry {
+                    path: "package/link",
+                    entry_type: tar::EntryType::Symlink,
+                    link_name: Some("/etc/passwd"),
+                    content: "",
+                },
+                "link",
+            ),
+            (
+                Entry {
+                    path: "package/hardlink",
+                    entry_type: tar::EntryType::Link,
+                    link_name: Some("package/index.js"),
+                    content: "",
+                },
+                "link",
+            ),
+            (
+                Entry {
+                    path: "package/device",
+                    entry_type: tar::EntryType::Char,
+                    link_name: None,
+                    content: "",
+                },
+                "unsupported entry type",
+            ),
+        ];
+
+        let tmp_dir = tempdir::TempDir::new("vouch_js_archive")?;
+        for (unsafe_entry, expected_error) in unsafe_entries {
+            let content = get_archive(&[get_file("package/index.js", "1"), unsafe_entry]);
+            let destination = tmp_dir.path().join("extracted");
+            let error = extract(&content, &destination).unwrap_err();
+            assert!(
+                error.to_string().contains(expected_error),
+                "unexpected error: {}",
+                error
+            );
+            assert!(!destination.exists());
+            assert_eq!(std::fs::read_dir(tmp_dir.path())?.count(), 0);
+        }
+        assert!(!tmp_dir.path().parent().unwrap().join("x").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract() -> Result<()> {
+        let tmp_dir = tempdir::TempDir::new("vouch_js_archive")?;
+        let destination = tmp_dir.path().join("extracted");
+        let content = get_archive(&[get_file("package/lib/index.js", "module.exports = 1;")]);
+        extract(&content, &destination)?;
+        assert_eq!(
+            std::fs::read_to_string(destination.join("lib").join("index.js"))?,
+            "module.exports = 1;"
+        );
+        assert_eq!(std::fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        // The destination must not exist.
+        assert!(extract(&content, &destination).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,426 @@
+use anyhow::{Context, Result};
+
+use crate::{integrity, npm, npmrc};
+
+/// Discrepancy between a lockfile entry and the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// Lockfile integrity hashes do not match the registry's archive hashes.
+    IntegrityMismatch { lockfile: String, registry: String },
+    /// Lockfile archive URL differs from the registry's archive URL.
+    ResolvedMismatch { lockfile: String, registry: String },
+    /// Lockfile archive URL is not served by a configured registry.
+    NonRegistryHost { resolved: String },
+    /// Lockfile entry has no integrity hash which can be checked against the registry.
+    MissingIntegrity,
+    /// The registry does not provide the package.
+    PackageNotFound { registry_url: String },
+    /// The registry does not provide the package version.
+    VersionNotFound { registry_url: String },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IntegrityMismatch { lockfile, registry } => write!(
+                f,
+                "integrity does not match the registry (lockfile: {}, registry: {})",
+                lockfile, registry
+            ),
+            Self::ResolvedMismatch { lockfile, registry } => write!(
+                f,
+                "resolved URL does not match the registry (lockfile: {}, registry: {})",
+                lockfile, registry
+            ),
+            Self::NonRegistryHost { resolved } => write!(
+                f,
+                "resolved URL is not served by a configured registry: {}",
+                resolved
+            ),
+            Self::MissingIntegrity => write!(f, "no integrity hash to check against the registry"),
+            Self::PackageNotFound { registry_url } => {
+                write!(f, "package not found in registry: {}", registry_url)
+            }
+            Self::VersionNotFound { registry_url } => {
+                write!(f, "version not found in registry: {}", registry_url)
+            }
+        }
+    }
+}
+
+/// Lockfile entry which does not agree with the registry.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub path: std::path::PathBuf,
+    pub package_name: String,
+    pub package_version: String,
+    pub finding: Finding,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}@{}: {}",
+            self.path.display(),
+            self.package_name,
+            self.package_version,
+            self.finding
+        )
+    }
+}
+
+/// Registry package version recorded in a lockfile.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub resolved: Option<url::Url>,
+    pub integrity: Option<String>,
+}
+
+impl Package {
+    /// Returns the registry package described by a lockfile entry.
+    ///
+    /// Returns `None` for entries which are not installed from a registry archive:
+    /// bundled packages, and git, file and other non-HTTP sources.
+    pub fn from_lockfile_entry(entry: npm::LockfileEntry) -> Option<Self> {
+        if entry.is_bundled {
+            return None;
+        }
+        let version = entry.version?;
+
+        // Aliased packages in lockfileVersion 1 record `npm:<name>@<version>` versions.
+        let (name, version) = match version.strip_prefix("npm:") {
+            Some(specifier) => {
+                let (name, version) = npm::split_specifier(specifier)?;
+                (name.to_string(), version.to_string())
+            }
+            None => (entry.name, version),
+        };
+        semver::Version::parse(&version).ok()?;
+
+        let resolved = match entry.resolved {
+            Some(resolved) => {
+                let url = url::Url::parse(&resolved).ok()?;
+                if url.scheme() != "https" && url.scheme() != "http" {
+                    return None;
+                }
+                Some(url)
+            }
+            None => None,
+        };
+
+        Some(Self {
+            name,
+            version,
+            resolved,
+            integrity: entry.integrity,
+        })
+    }
+}
+
+/// Compare a lockfile package against the registry entry (packument) of its primary registry.
+///
+/// The `resolved` URL must be served by a configured registry, or by the archive host the
+/// registry advertises, and must match the registry's archive URL. The lockfile integrity
+/// must match the registry's archive hashes.
+pub fn check_package(
+    package: &Package,
+    registry_url: &url::Url,
+    registry_entry_json: Option<&serde_json::Value>,
+    registry_urls: &[url::Url],
+) -> Result<Vec<Finding>> {
+    let registry_entry_json = match registry_entry_json {
+        Some(v) => v,
+        None => {
+            return Ok(vec![Finding::PackageNotFound {
+                registry_url: registry_url.to_string(),
+            }])
+        }
+    };
+    if registry_entry_json["versions"][&package.version].is_null() {
+        return Ok(vec![Finding::VersionNotFound {
+            registry_url: registry_url.to_string(),
+        }]);
+    }
+    let archive = crate::get_package_archive(registry_url, registry_entry_json, &package.version)?;
+
+    let mut findings = Vec::new();
+    if let Some(resolved) = &package.resolved {
+        let mut allowed_urls: Vec<&url::Url> = registry_urls.iter().collect();
+        allowed_urls.push(&archive.url);
+        if !allowed_urls.iter().any(|url| is_same_host(url, resolved)) {
+            findings.push(Finding::NonRegistryHost {
+                resolved: resolved.to_string(),
+            });
+        } else if !(is_same_host(resolved, &archive.url) && resolved.path() == archive.url.path()) {
+            findings.push(Finding::ResolvedMismatch {
+                lockfile: resolved.to_string(),
+                registry: archive.url.to_string(),
+            });
+        }
+    }
+
+    let lockfile_integrity = match &package.integrity {
+        Some(v) => integrity::Integrity::parse(v).context(format!(
+            "Failed to parse lockfile integrity of package {}@{}",
+            package.name, package.version
+        ))?,
+        None => integrity::Integrity::default(),
+    };
+    let registry_integrity = archive.get_integrity()?;
+    match lockfile_integrity.matches(&registry_integrity) {
+        Some(true) => {}
+        Some(false) => findings.push(Finding::IntegrityMismatch {
+            lockfile: lockfile_integrity.to_string(),
+            registry: registry_integrity.to_string(),
+        }),
+        None => findings.push(Finding::MissingIntegrity),
+    }
+    Ok(findings)
+}
+
+/// Returns true if both URLs have the same scheme and host.
+///
+/// The default registry host names are interchangeable.
+fn is_same_host(a: &url::Url, b: &url::Url) -> bool {
+    if npmrc::is_default_registry(a) && npmrc::is_default_registry(b) {
+        return a.scheme() == b.scheme();
+    }
+    a.scheme() == b.scheme() && a.host_str() == b.host_str() && a.port() == b.port()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_hash(algorithm: integrity::Algorithm, data: &[u8]) -> String {
+        integrity::Hash {
+            algorithm,
+            digest: algorithm.digest(data),
+        }
+        .to_string()
+    }
+
+    fn get_registry_entry_json() -> serde_json::Value {
+        serde_json::json!({
+            "name": "d3",
+            "versions": {
+                "6.5.0": {
+                    "dist": {
+                        "tarball": "https://registry.npmjs.org/d3/-/d3-6.5.0.tgz",
+                        "integrity": get_hash(integrity::Algorithm::Sha512, b"d3-6.5.0"),
+                        "shasum": integrity::Algorithm::Sha1
+                            .digest(b"d3-6.5.0")
+                            .iter()
+                            .map(|byte| format!("{:02x}", byte))
+                            .collect::<String>()
+                    }
+                }
+            }
+        })
+    }
+
+    fn get_package(version: &str, resolved: Option<&str>, integrity: Option<&str>) -> Package {
+        Package {
+            name: "d3".to_string(),
+            version: version.to_string(),
+            resolved: resolved.map(|v| url::Url::parse(v).unwrap()),
+            integrity: integrity.map(|v| v.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_check_package() -> Result<()> {
+        let registry_url = url::Url::parse("https://registry.npmjs.com/")?;
+        let registry_urls = vec![
+            registry_url.clone(),
+            url::Url::parse("https://mirror.example.com/npm/")?,
+        ];
+        let registry_entry_json = get_registry_entry_json();
+        let sha512 = get_hash(integrity::Algorithm::Sha512, b"d3-6.5.0");
+        let sha1 = get_hash(integrity::Algorithm::Sha1, b"d3-6.5.0");
+        let other_sha512 = get_hash(integrity::Algorithm::Sha512, b"other");
+        let resolved = "https://registry.npmjs.org/d3/-/d3-6.5.0.tgz";
+
+        let cases = vec![
+            (get_package("6.5.0", Some(resolved), Some(&sha512)), vec![]),
+            // The default registry host names are interchangeable.
+            (
+                get_package(
+                    "6.5.0",
+                    Some("https://registry.npmjs.com/d3/-/d3-6.5.0.tgz"),
+                    Some(&sha512),
+                ),
+                vec![],
+            ),
+            // Legacy sha1 hashes are compared if no stronger algorithm is shared.
+            (get_package("6.5.0", None, Some(&sha1)), vec![]),
+            (
+                get_package(
+                    "6.5.0",
+                    Some("https://registry.npmjs.org/d3/-/d3-6.4.0.tgz"),
+                    Some(&sha512),
+                ),
+                vec![Finding::ResolvedMismatch {
+                    lockfile: "https://registry.npmjs.org/d3/-/d3-6.4.0.tgz".to_string(),
+                    registry: resolved.to_string(),
+                }],
+            ),
+            (
+                get_package(
+                    "6.5.0",
+                    Some("https://mirror.example.com/npm/d3/-/d3-6.5.0.tgz"),
+                    Some(&sha512),
+                ),
+                vec![Finding::ResolvedMismatch {
+                    lockfile: "https://mirror.example.com/npm/d3/-/d3-6.5.0.tgz".to_string(),
+                    registry: resolved.to_string(),
+                }],
+            ),
+            (
+                get_package(
+                    "6.5.0",
+                    Some("https://evil.example.com/d3-6.5.0.tgz"),
+                    Some(&sha512),
+                ),
+                vec![Finding::NonRegistryHost {
+                    resolved: "https://evil.example.com/d3-6.5.0.tgz".to_string(),
+                }],
+            ),
+            (
+                get_package(
+                    "6.5.0",
+                    Some("http://registry.npmjs.org/d3/-/d3-6.5.0.tgz"),
+                    Some(&sha512),
+                ),
+                vec![Finding::NonRegistryHost {
+                    resolved: "http://registry.npmjs.org/d3/-/d3-6.5.0.tgz".to_string(),
+                }],
+            ),
+            (
+                get_package("6.5.0", Some(resolved), Some(&other_sha512)),
+                vec![Finding::IntegrityMismatch {
+                    lockfile: other_sha512.clone(),
+                    registry: format!("{} {}", sha512, sha1),
+                }],
+            ),
+            (
+                get_package("6.5.0", Some(resolved), None),
+                vec![Finding::MissingIntegrity],
+            ),
+            (
+                get_package("7.0.0", Some(resolved), Some(&sha512)),
+                vec![Finding::VersionNotFound {
+                    registry_url: registry_url.to_string(),
+                }],
+            ),
+        ];
+        for (package, expected_findings) in cases {
+            let findings = check_package(
+                &package,
+                &registry_url,
+                Some(&registry_entry_json),
+                &registry_urls,
+            )?;
+            assert_eq!(findings, expected_findings, "{:?}", package);
+        }
+
+        let findings = check_package(
+            &get_package("6.5.0", Some(resolved), Some(&sha512)),
+            &registry_url,
+            None,
+            &registry_urls,
+        )?;
+        assert_eq!(
+            findings,
+            vec![Finding::PackageNotFound {
+                registry_url: registry_url.to_string(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_same_host() -> Result<()> {
+        let parse = |url: &str| url::Url::parse(url).unwrap();
+        assert!(is_same_host(
+            &parse("https://registry.npmjs.org/d3"),
+            &parse("https://registry.npmjs.com/")
+        ));
+        assert!(!is_same_host(
+            &parse("http://registry.npmjs.org/d3"),
+            &parse("https://registry.npmjs.com/")
+        ));
+        assert!(is_same_host(
+            &parse("https://npm.example.com/a"),
+            &parse("https://npm.example.com/b")
+        ));
+        assert!(!is_same_host(
+            &parse("https://npm.example.com:8443/"),
+            &parse("https://npm.example.com/")
+        ));
+        assert!(!is_same_host(
+            &parse("https://npm.example.com/"),
+            &parse("https://registry.npmjs.org/")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_from_lockfile_entry() {
+        let get_entry = |name: &str, version: &str, resolved: Option<&str>| npm::LockfileEntry {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            resolved: resolved.map(|v| v.to_string()),
+            integrity: Some("sha512-AAAA".to_string()),
+            is_bundled: false,
+        };
+
+        let package = Package::from_lockfile_entry(get_entry(
+            "d3",
+            "6.5.0",
+            Some("https://registry.npmjs.org/d3/-/d3-6.5.0.tgz"),
+        ))
+        .unwrap();
+        assert_eq!(package.name, "d3");
+        assert_eq!(package.version, "6.5.0");
+        assert_eq!(
+            package.resolved.map(|url| url.to_string()),
+            Some("https://registry.npmjs.org/d3/-/d3-6.5.0.tgz".to_string())
+        );
+        assert_eq!(package.integrity.as_deref(), Some("sha512-AAAA"));
+
+        // Aliases in lockfileVersion 1 record the aliased package name and version.
+        let package = Package::from_lockfile_entry(get_entry(
+            "string-width-cjs",
+            "npm:string-width@4.2.3",
+            None,
+        ))
+        .unwrap();
+        assert_eq!(package.name, "string-width");
+        assert_eq!(package.version, "4.2.3");
+        assert!(package.resolved.is_none());
+
+        let mut bundled_entry = get_entry("d3", "6.5.0", None);
+        bundled_entry.is_bundled = true;
+        let skipped_entries = vec![
+            bundled_entry,
+            get_entry(
+                "my-lib",
+                "1.0.0",
+                Some("git+ssh://git@github.com/owner/my-lib.git#0123abc"),
+            ),
+            get_entry("my-lib", "git+ssh://git@github.com/owner/my-lib.git", None),
+            get_entry("local", "file:../local", None),
+        ];
+        for entry in skipped_entries {
+            assert!(
+                Package::from_lockfile_entry(entry.clone()).is_none(),
+                "{:?}",
+                entry
+            );
+        }
+    }
+}
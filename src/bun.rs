@@ -0,0 +1,349 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::{HashSet, VecDeque};
+
+use crate::npm;
+
+type JsonObject = serde_json::Map<String, serde_json::Value>;
+
+/// Convert JSONC content into strict JSON.
+///
+/// Removes line and block comments and trailing commas which appear outside of strings.
+fn strip_jsonc(content: &str) -> String {
+    let mut json = String::with_capacity(content.len());
+    let mut characters = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(character) = characters.next() {
+        if in_string {
+            json.push(character);
+            if character == '\\' {
+                if let Some(escaped) = characters.next() {
+                    json.push(escaped);
+                }
+            } else if character == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match character {
+            '"' => {
+                in_string = true;
+                json.push(character);
+            }
+            '/' if characters.peek() == Some(&'/') => {
+                for next in characters.by_ref() {
+                    if next == '\n' {
+                        json.push(next);
+                        break;
+                    }
+                }
+            }
+            '/' if characters.peek() == Some(&'*') => {
+                characters.next();
+                let mut previous = None;
+                for next in characters.by_ref() {
+                    if previous == Some('*') && next == '/' {
+                        break;
+                    }
+                    previous = Some(next);
+                }
+            }
+            '}' | ']' => {
+                // Remove trailing comma preceding the closing bracket.
+                let trimmed_length = json.trim_end().len();
+                if json[..trimmed_length].ends_with(',') {
+                    json.truncate(trimmed_length - 1);
+                }
+                json.push(character);
+            }
+            _ => json.push(character),
+        }
+    }
+    json
+}
+
+/// Split a `packages` section key into its package name components.
+///
+/// Nested packages are keyed by their parent path. Example: `a/@scope/b` yields
+/// `["a", "@scope/b"]`.
+fn split_package_key(key: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut segments = key.split('/');
+    while let Some(segment) = segments.next() {
+        if segment.starts_with('@') {
+            if let Some(name) = segments.next() {
+                names.push(format!("{}/{}", segment, name));
+                continue;
+            }
+        }
+        names.push(segment.to_string());
+    }
+    names
+}
+
+/// Resolve a dependency name to a `packages` section key.
+///
+/// Mirrors node_modules resolution: the nested key under the dependent package
+/// is preferred, then each parent path in turn, then the hoisted key.
+fn resolve_package_key(packages: &JsonObject, parent_key: &str, name: &str) -> Option<String> {
+    let mut parent_names = split_package_key(parent_key);
+    loop {
+        let candidate_key = if parent_names.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", parent_names.join("/"), name)
+        };
+        if packages.contains_key(&candidate_key) {
+            return Some(candidate_key);
+        }
+        parent_names.pop()?;
+    }
+}
+
+/// Returns the package metadata object from a `packages` section tuple.
+///
+/// Registry package tuples have the form `[specifier, registry, metadata, integrity]`.
+/// Other package tuples omit the registry element.
+fn get_package_metadata(package: &[serde_json::Value]) -> Option<&JsonObject> {
+    package.iter().skip(1).find_map(|value| value.as_object())
+}
+
+/// Returns non-dev root package keys from all workspaces.
+fn get_root_package_keys(lockfile: &serde_json::Value, packages: &JsonObject) -> Vec<String> {
+    let mut package_keys = Vec::new();
+    let workspaces = match lockfile["workspaces"].as_object() {
+        Some(v) => v,
+        None => return package_keys,
+    };
+    for workspace in workspaces.values() {
+        let workspace_name = workspace["name"].as_str().unwrap_or_default();
+        for section in &["dependencies", "optionalDependencies", "peerDependencies"] {
+            if let Some(dependencies) = workspace[*section].as_object() {
+                for name in dependencies.keys() {
+                    if let Some(package_key) = resolve_package_key(packages, workspace_name, name) {
+                        package_keys.push(package_key);
+                    }
+                }
+            }
+        }
+    }
+    package_keys
+}
+
+/// Returns package keys reachable from the given root keys.
+fn get_reachable_package_keys(
+    packages: &JsonObject,
+    root_package_keys: Vec<String>,
+) -> Vec<String> {
+    let mut visited = HashSet::new();
+    let mut unprocessed_keys: VecDeque<String> = root_package_keys.into_iter().collect();
+    while let Some(key) = unprocessed_keys.pop_front() {
+        if visited.contains(&key) {
+            continue;
+        }
+        let metadata = packages[&key]
+            .as_array()
+            .and_then(|package| get_package_metadata(package));
+        if let Some(metadata) = metadata {
+            for section in &["dependencies", "optionalDependencies", "peerDependencies"] {
+                if let Some(dependencies) = metadata.get(*section).and_then(|v| v.as_object()) {
+                    for name in dependencies.keys() {
+                        if let Some(package_key) = resolve_package_key(packages, &key, name) {
+                            unprocessed_keys.push_back(package_key);
+                        }
+                    }
+                }
+            }
+        }
+        visited.insert(key);
+    }
+    visited.into_iter().collect()
+}
+
+/// Parse dependencies from a bun.lock file.
+///
+/// Packages resolved through protocols other than the npm registry (`workspace:`,
+/// `github:`, `file:`, ...) are excluded by protocol.
+///
+/// bun.lock does not record dev dependency flags. Dev dependencies are therefore
+/// excluded by walking the dependency graph from non-dev workspace dependencies.
+pub fn get_dependencies(
+    file_path: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<npm::FileDependencies> {
+    let content = std::fs::read_to_string(file_path)?;
+    let lockfile: serde_json::Value = serde_json::from_str(&strip_jsonc(&content))
+        .context(format!("Failed to parse bun.lock: {}", file_path.display()))?;
+
+    let empty_packages = JsonObject::new();
+    let packages = lockfile["packages"].as_object().unwrap_or(&empty_packages);
+    let package_keys = if include_dev_dependencies {
+        packages.keys().cloned().collect()
+    } else {
+        let root_package_keys = get_root_package_keys(&lockfile, packages);
+        get_reachable_package_keys(packages, root_package_keys)
+    };
+
+    let mut all_dependencies = npm::FileDependencies::default();
+    for package_key in package_keys {
+        let specifier = packages[&package_key][0].as_str().ok_or(format_err!(
+            "Failed to parse bun.lock package entry: {}",
+            package_key
+        ))?;
+        let (name, version) = match npm::split_specifier(specifier) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        // Registry package specifiers contain a plain version number.
+        let protocol = version
+            .split_once(':')
+            .map(|(protocol, _)| protocol.to_string());
+        all_dependencies.insert(
+            protocol,
+            vouch_lib::extension::Dependency {
+                name: name.to_string(),
+                version: npm::get_parsed_version(&Some(version)),
+            },
+        );
+    }
+    Ok(all_dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, get_names_versions};
+
+    static LOCKFILE: &str = r#"{
+  "lockfileVersion": 1,
+  "workspaces": {
+    "": {
+      "name": "my-app",
+      "dependencies": {
+        "my-lib": "github:owner/my-lib",
+        "react-dom": "^17.0.2",
+      },
+      "devDependencies": {
+        "jest": "^27.0.0",
+      },
+    },
+  },
+  "packages": {
+    // Hoisted packages.
+    "jest": ["jest@27.4.5", "", { "dependencies": { "jest-util": "^27.4.2" } }, "sha512-AAAA"],
+
+    "jest-util": ["jest-util@27.4.2", "", {}, "sha512-AAAA"],
+
+    "my-lib": ["my-lib@github:owner/my-lib#0123abc", {}, "owner-my-lib-0123abc"],
+
+    "react": ["react@17.0.2", "", {}, "sha512-AAAA"],
+
+    "react-dom": ["react-dom@17.0.2", "", { "dependencies": { "scheduler": "^0.19.1" }, "peerDependencies": { "react": "17.0.2" } }, "sha512-AAAA"],
+
+    "scheduler": ["scheduler@0.20.2", "", {}, "sha512-AAAA"],
+
+    /* Nested packages. */
+    "react-dom/scheduler": ["scheduler@0.19.1", "", {}, "sha512-AAAA"],
+  }
+}
+"#;
+
+    #[test]
+    fn test_strip_jsonc() -> Result<()> {
+        let cases = vec![
+            ("{\"a\": 1, // comment\n}", serde_json::json!({"a": 1})),
+            (
+                "{/* block\ncomment */\"a\": [1, 2,],}",
+                serde_json::json!({"a": [1, 2]}),
+            ),
+            (
+                "{\"a\": \"x, }\", \"b\": [\",\", ],}",
+                serde_json::json!({"a": "x, }", "b": [","]}),
+            ),
+            (
+                "{\"a\": \"// not a comment\", \"b\": \"/* nor this */\"}",
+                serde_json::json!({"a": "// not a comment", "b": "/* nor this */"}),
+            ),
+            (
+                "{\"a\": \"quote \\\" // inside\",\n}",
+                serde_json::json!({"a": "quote \" // inside"}),
+            ),
+        ];
+        for (content, expected) in cases {
+            let value: serde_json::Value = serde_json::from_str(&strip_jsonc(content))?;
+            assert_eq!(value, expected, "{}", content);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_package_key() {
+        assert_eq!(
+            split_package_key("a/@scope/b/c"),
+            vec!["a", "@scope/b", "c"]
+        );
+        assert_eq!(split_package_key("@scope/b"), vec!["@scope/b"]);
+    }
+
+    #[test]
+    fn test_resolve_package_key() {
+        let packages: JsonObject = serde_json::from_value(serde_json::json!({
+            "c": [],
+            "a/c": [],
+            "a/@scope/b": [],
+            "a/@scope/b/d": [],
+            "@scope/e": [],
+        }))
+        .unwrap();
+        let cases = vec![
+            // Nested key under the dependent package.
+            ("a/@scope/b", "d", Some("a/@scope/b/d")),
+            // Parent path.
+            ("a/@scope/b", "c", Some("a/c")),
+            // Hoisted key.
+            ("a", "@scope/e", Some("@scope/e")),
+            ("", "c", Some("c")),
+            ("a", "missing", None),
+        ];
+        for (parent_key, name, expected_key) in cases {
+            assert_eq!(
+                resolve_package_key(&packages, parent_key, name).as_deref(),
+                expected_key,
+                "{}: {}",
+                parent_key,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_dependencies() -> Result<()> {
+        let (_project_directory, file_path) = testing::write_lockfile("bun.lock", LOCKFILE, None)?;
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            vec!["react@17.0.2", "react-dom@17.0.2", "scheduler@0.19.1"]
+        );
+        assert_eq!(
+            get_names_versions(&dependencies.excluded_dependencies["github"]),
+            vec!["my-lib@github:owner/my-lib#0123abc"]
+        );
+
+        let (_project_directory, file_path) = testing::write_lockfile("bun.lock", LOCKFILE, None)?;
+        let dependencies = get_dependencies(&file_path, true)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            vec![
+                "jest@27.4.5",
+                "jest-util@27.4.2",
+                "react@17.0.2",
+                "react-dom@17.0.2",
+                "scheduler@0.19.1",
+                "scheduler@0.20.2"
+            ]
+        );
+        Ok(())
+    }
+}
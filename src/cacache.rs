@@ -0,0 +1,290 @@
+use anyhow::{format_err, Result};
+use sha2::Digest;
+
+use crate::npmrc;
+
+/// Key prefix used by npm (make-fetch-happen) for cached HTTP responses.
+static REQUEST_CACHE_KEY_PREFIX: &str = "make-fetch-happen:request-cache:";
+
+/// Index entry of npm's content-addressable cache.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct IndexEntry {
+    key: String,
+    integrity: Option<String>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+/// Returns npm's cache directory, configured with npm's `cache` option.
+///
+/// Defaults to `~/.npm` (`%LocalAppData%/npm-cache` on Windows).
+fn get_cache_directory(npm_config: &npmrc::Config) -> Option<std::path::PathBuf> {
+    if let Some(directory) = npm_config.get("cache") {
+        return Some(std::path::PathBuf::from(directory));
+    }
+    if cfg!(windows) {
+        dirs::data_local_dir().map(|v| v.join("npm-cache"))
+    } else {
+        dirs::home_dir().map(|v| v.join(".npm"))
+    }
+}
+
+/// Split a hex digest into cache path segments: `ab/cd/ef...`.
+fn get_hash_path(directory: &std::path::Path, hash: &str) -> Option<std::path::PathBuf> {
+    Some(
+        directory
+            .join(hash.get(..2)?)
+            .join(hash.get(2..4)?)
+            .join(hash.get(4..)?),
+    )
+}
+
+/// Returns the most recent index entry for a key.
+///
+/// Index buckets are append-only. Each line holds a hash of the entry and the JSON
+/// encoded entry, separated by a tab. Entries without integrity mark deletions.
+fn find_index_entry(cache_directory: &std::path::Path, key: &str) -> Option<IndexEntry> {
+    let key_hash = format!("{:x}", sha2::Sha256::digest(key.as_bytes()));
+    let bucket_path = get_hash_path(&cache_directory.join("index-v5"), &key_hash)?;
+    let bucket = std::fs::read_to_string(bucket_path).ok()?;
+
+    bucket
+        .lines()
+        .rev()
+        .filter_map(|line| line.split_once('\t'))
+        .filter_map(|(_, entry)| serde_json::from_str::<IndexEntry>(entry).ok())
+        .find(|entry| entry.key == key)
+}
+
+/// Read cached content given its subresource integrity string.
+///
+/// Only sha512 content is read, and the content is verified against its hash.
+fn read_content(cache_directory: &std::path::Path, integrity: &str) -> Result<Option<Vec<u8>>> {
+    for hash in integrity.split_whitespace() {
+        let digest = match hash.strip_prefix("sha512-") {
+            Some(v) => v.split('?').next().unwrap_or_default(),
+            None => continue,
+        };
+        let digest = base64::decode(digest)
+            .map_err(|_| format_err!("Invalid integrity value in npm cache: {}", hash))?;
+        let hex_digest: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        let content_path =
+            match get_hash_path(&cache_directory.join("content-v2/sha512"), &hex_digest) {
+                Some(v) => v,
+                None => continue,
+            };
+        let content = match std::fs::read(&content_path) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if sha2::Sha512::digest(&content)[..] != digest[..] {
+            return Err(format_err!(
+                "npm cache content is corrupt: {}\nRun `npm cache verify` and try again.",
+                content_path.display()
+            ));
+        }
+        return Ok(Some(content));
+    }
+    Ok(None)
+}
+
+/// Returns content from npm's cache given its Subresource Integrity string.
+///
+/// npm stores package archives by content hash, so they can be found without their URL.
+pub fn get_content(npm_config: &npmrc::Config, integrity: &str) -> Result<Option<Vec<u8>>> {
+    match get_cache_directory(npm_config) {
+        Some(v) => read_content(&v.join("_cacache"), integrity),
+        None => Ok(None),
+    }
+}
+
+/// Returns a registry response body from npm's cache (`~/.npm/_cacache`).
+///
+/// npm caches registry responses keyed by request URL. Returns the response body and
+/// the cached request and response metadata, or `None` if npm has not cached the URL.
+pub fn get(
+    npm_config: &npmrc::Config,
+    url: &url::Url,
+) -> Result<Option<(Vec<u8>, serde_json::Value)>> {
+    let cache_directory = match get_cache_directory(npm_config) {
+        Some(v) => v.join("_cacache"),
+        None => return Ok(None),
+    };
+    let key = format!("{}{}", REQUEST_CACHE_KEY_PREFIX, url);
+    let entry = match find_index_entry(&cache_directory, &key) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let integrity = match &entry.integrity {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    Ok(read_content(&cache_directory, integrity)?.map(|content| (content, entry.metadata)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Append index entries for a key to its index-v5 bucket.
+    fn write_index_entries(
+        cache_directory: &std::path::Path,
+        key: &str,
+        entries: &[serde_json::Value],
+    ) -> Result<()> {
+        let key_hash = format!("{:x}", sha2::Sha256::digest(key.as_bytes()));
+        let bucket_path = get_hash_path(&cache_directory.join("index-v5"), &key_hash).unwrap();
+        std::fs::create_dir_all(bucket_path.parent().unwrap())?;
+        let mut bucket = std::fs::read_to_string(&bucket_path).unwrap_or_default();
+        for entry in entries {
+            let entry = entry.to_string();
+            bucket.push_str(&format!(
+                "\n{:x}\t{}",
+                sha2::Sha256::digest(entry.as_bytes()),
+                entry
+            ));
+        }
+        std::fs::write(bucket_path, bucket)?;
+        Ok(())
+    }
+
+    /// Store content under its content-v2 hash path and return its integrity string.
+    fn write_content(cache_directory: &std::path::Path, content: &[u8]) -> Result<String> {
+        let digest = sha2::Sha512::digest(content);
+        let hex_digest: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        let content_path =
+            get_hash_path(&cache_directory.join("content-v2/sha512"), &hex_digest).unwrap();
+        std::fs::create_dir_all(content_path.parent().unwrap())?;
+        std::fs::write(content_path, content)?;
+        Ok(format!("sha512-{}", base64::encode(digest)))
+    }
+
+    fn get_npm_config(directory: &std::path::Path) -> npmrc::Config {
+        testing::get_npm_config(&format!("cache={}\n", directory.display()))
+    }
+
+    #[test]
+    fn test_get() -> Result<()> {
+        let directory = tempdir::TempDir::new("vouch_js_test")?;
+        let cache_directory = directory.path().join("_cacache");
+        let npm_config = get_npm_config(directory.path());
+        let url = url::Url::parse("https://registry.npmjs.org/d3")?;
+        let key = format!("{}{}", REQUEST_CACHE_KEY_PREFIX, url);
+
+        assert!(get(&npm_config, &url)?.is_none());
+
+        let old_integrity = write_content(&cache_directory, br#"{"name":"d3","old":true}"#)?;
+        let integrity = write_content(&cache_directory, br#"{"name":"d3"}"#)?;
+        let metadata = serde_json::json!({"reqHeaders": {"accept": "application/json"}});
+        write_index_entries(
+            &cache_directory,
+            &key,
+            &[
+                serde_json::json!({"key": key, "integrity": old_integrity}),
+                serde_json::json!({"key": "make-fetch-happen:request-cache:other", "integrity": old_integrity}),
+                serde_json::json!({"key": key, "integrity": integrity, "metadata": metadata}),
+            ],
+        )?;
+        assert_eq!(
+            get(&npm_config, &url)?,
+            Some((br#"{"name":"d3"}"#.to_vec(), metadata))
+        );
+        assert!(get(
+            &npm_config,
+            &url::Url::parse("https://registry.npmjs.org/d4")?
+        )?
+        .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_deleted() -> Result<()> {
+        let directory = tempdir::TempDir::new("vouch_js_test")?;
+        let cache_directory = directory.path().join("_cacache");
+        let npm_config = get_npm_config(directory.path());
+        let url = url::Url::parse("https://registry.npmjs.org/d3")?;
+        let key = format!("{}{}", REQUEST_CACHE_KEY_PREFIX, url);
+
+        let integrity = write_content(&cache_directory, br#"{"name":"d3"}"#)?;
+        write_index_entries(
+            &cache_directory,
+            &key,
+            &[
+                serde_json::json!({"key": key, "integrity": integrity}),
+                serde_json::json!({"key": key, "integrity": null}),
+            ],
+        )?;
+        assert!(get(&npm_config, &url)?.is_none());
+
+        // Entries written after a deletion are found, and malformed lines are ignored.
+        write_index_entries(
+            &cache_directory,
+            &key,
+            &[serde_json::json!({"key": key, "integrity": integrity})],
+        )?;
+        let mut bucket_file = std::fs::OpenOptions::new().append(true).open(
+            get_hash_path(
+                &cache_directory.join("index-v5"),
+                &format!("{:x}", sha2::Sha256::digest(key.as_bytes())),
+            )
+            .unwrap(),
+        )?;
+        std::io::Write::write_all(&mut bucket_file, b"\nmalformed\t{\"key\":")?;
+        assert!(get(&npm_config, &url)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_content() -> Result<()> {
+        let directory = tempdir::TempDir::new("vouch_js_test")?;
+        let cache_directory = directory.path().join("_cacache");
+        let npm_config = get_npm_config(directory.path());
+
+        let integrity = write_content(&cache_directory, b"archive")?;
+        let sha1_integrity = "sha1-3l9n5wYHn0pEzpNwhVeeJaj3RZE=";
+        assert_eq!(
+            get_content(&npm_config, &format!("{} {}", sha1_integrity, integrity))?,
+            Some(b"archive".to_vec())
+        );
+        assert!(get_content(&npm_config, sha1_integrity)?.is_none());
+        assert!(get_content(&npm_config, "sha512-not*base64").is_err());
+
+        let missing_integrity = format!(
+            "sha512-{}",
+            base64::encode(sha2::Sha512::digest(b"missing"))
+        );
+        assert!(get_content(&npm_config, &missing_integrity)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_corrupt_content() -> Result<()> {
+        let directory = tempdir::TempDir::new("vouch_js_test")?;
+        let cache_directory = directory.path().join("_cacache");
+        let npm_config = get_npm_config(directory.path());
+        let url = url::Url::parse("https://registry.npmjs.org/d3")?;
+        let key = format!("{}{}", REQUEST_CACHE_KEY_PREFIX, url);
+
+        let integrity = write_content(&cache_directory, br#"{"name":"d3"}"#)?;
+        write_index_entries(
+            &cache_directory,
+            &key,
+            &[serde_json::json!({"key": key, "integrity": integrity})],
+        )?;
+        let hex_digest: String = sha2::Sha512::digest(br#"{"name":"d3"}"#)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let content_path =
+            get_hash_path(&cache_directory.join("content-v2/sha512"), &hex_digest).unwrap();
+        std::fs::write(content_path, br#"{"name":"d4"}"#)?;
+
+        let error = get(&npm_config, &url).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("npm cache content is corrupt"));
+        assert!(get_content(&npm_config, &integrity).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::{format_err, Context, Result};
+
+use crate::{npmrc, package_name, PackumentFormat};
+
+/// Default time for which a cached packument is used without revalidation.
+static DEFAULT_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Cached registry response and the validators needed to revalidate it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+
+    /// Seconds since the Unix epoch when the entry was last fetched or revalidated.
+    pub fetched_at: u64,
+    pub body: serde_json::Value,
+}
+
+impl Entry {
+    pub fn new(
+        url: &url::Url,
+        headers: &reqwest::header::HeaderMap,
+        body: serde_json::Value,
+    ) -> Self {
+        let get_header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        Self {
+            url: url.to_string(),
+            etag: get_header(reqwest::header::ETAG),
+            last_modified: get_header(reqwest::header::LAST_MODIFIED),
+            fetched_at: get_unix_time(),
+            body,
+        }
+    }
+
+    /// Returns conditional request headers which revalidate this entry.
+    pub fn get_revalidation_headers(&self) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(value) = self
+            .etag
+            .as_ref()
+            .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok())
+        {
+            headers.insert(reqwest::header::IF_NONE_MATCH, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .as_ref()
+            .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok())
+        {
+            headers.insert(reqwest::header::IF_MODIFIED_SINCE, value);
+        }
+        headers
+    }
+
+    /// Mark the entry as revalidated by the registry.
+    pub fn refresh(&mut self) {
+        self.fetched_at = get_unix_time();
+    }
+}
+
+/// On-disk cache of registry packuments.
+///
+/// Entries are keyed by document format, registry URL and package name. The time for which an entry
+/// is used without revalidation is configured with npm's `cache-min` (seconds),
+/// `prefer-online` and `prefer-offline` options. In offline mode, entries are
+/// never revalidated.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    directory: Option<std::path::PathBuf>,
+    max_age: Option<std::time::Duration>,
+}
+
+impl Cache {
+    pub fn new(npm_config: &npmrc::Config) -> Result<Self> {
+        let max_age = if npm_config.is_offline() {
+            None
+        } else if npm_config.is_enabled("prefer-online") {
+            Some(std::time::Duration::from_secs(0))
+        } else if npm_config.is_enabled("prefer-offline") {
+            None
+        } else {
+            match npm_config.get("cache-min") {
+                Some(value) => {
+                    Some(std::time::Duration::from_secs(value.parse().context(
+                        format!("Invalid npm config cache-min value: {}", value),
+                    )?))
+                }
+                None => Some(DEFAULT_MAX_AGE),
+            }
+        };
+
+        Ok(Self {
+            directory: dirs::cache_dir().map(|v| v.join("vouch-js").join("packuments")),
+            max_age,
+        })
+    }
+
+    /// Returns the entry file path for a registry package.
+    ///
+    /// Example: `~/.cache/vouch-js/packuments/full/https%3A%2F%2Fregistry.npmjs.com%2F/@babel%2fcore.json`
+    fn get_path(
+        &self,
+        registry_url: &url::Url,
+        package_name: &str,
+        format: PackumentFormat,
+    ) -> Option<std::path::PathBuf> {
+        let registry_directory: String =
+            url::form_urlencoded::byte_serialize(registry_url.as_str().as_bytes()).collect();
+        Some(
+            self.directory
+                .as_ref()?
+                .join(format.name())
+                .join(registry_directory)
+                .join(format!("{}.json", package_name::escape(package_name))),
+        )
+    }
+
+    /// Returns the cached entry for a registry package, if any.
+    ///
+    /// Unreadable entries are treated as missing.
+    pub fn get(
+        &self,
+        registry_url: &url::Url,
+        package_name: &str,
+        format: PackumentFormat,
+    ) -> Option<Entry> {
+        let path = self.get_path(registry_url, package_name, format)?;
+        let file = std::fs::File::open(path).ok()?;
+        serde_json::from_reader(std::io::BufReader::new(file)).ok()
+    }
+
+    /// Returns true if the entry can be used without revalidation.
+    pub fn is_fresh(&self, entry: &Entry) -> bool {
+        match self.max_age {
+            Some(max_age) => get_unix_time().saturating_sub(entry.fetched_at) < max_age.as_secs(),
+            None => true,
+        }
+    }
+
+    /// Store an entry for a registry package.
+    pub fn insert(
+        &self,
+        registry_url: &url::Url,
+        package_name: &str,
+        format: PackumentFormat,
+        entry: &Entry,
+    ) -> Result<()> {
+        let path = self
+            .get_path(registry_url, package_name, format)
+            .ok_or(format_err!("Failed to find cache directory."))?;
+        let directory = path
+            .parent()
+            .ok_or(format_err!("Invalid cache path: {}", path.display()))?;
+        std::fs::create_dir_all(directory)?;
+
+        // Write to a temporary file first so that concurrent readers never see partial entries.
+        let temporary_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&temporary_path, serde_json::to_vec(entry)?)?;
+        std::fs::rename(&temporary_path, &path)
+            .context(format!("Failed to write cache entry: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Remove the entry for a registry package, if any.
+    pub fn remove(&self, registry_url: &url::Url, package_name: &str, format: PackumentFormat) {
+        if let Some(path) = self.get_path(registry_url, package_name, format) {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+fn get_unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn get_entry(fetched_at: u64) -> Entry {
+        Entry {
+            url: "https://registry.npmjs.com/d3".to_string(),
+            etag: None,
+            last_modified: None,
+            fetched_at,
+            body: serde_json::json!({"name": "d3"}),
+        }
+    }
+
+    #[test]
+    fn test_is_fresh() -> Result<()> {
+        let now = get_unix_time();
+        let cases = vec![
+            ("", now, true),
+            ("", now - 10 * 60, false),
+            ("cache-min=3600\n", now - 10 * 60, true),
+            ("cache-min=60\n", now - 10 * 60, false),
+            ("prefer-online=true\n", now, false),
+            ("prefer-offline=true\n", 0, true),
+            ("offline=true\n", 0, true),
+        ];
+        for (content, fetched_at, expected) in cases {
+            let cache = Cache::new(&testing::get_npm_config(content))?;
+            assert_eq!(
+                cache.is_fresh(&get_entry(fetched_at)),
+                expected,
+                "{:?}",
+                content
+            );
+        }
+
+        assert!(Cache::new(&testing::get_npm_config("cache-min=soon\n")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_revalidation_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::ETAG,
+            reqwest::header::HeaderValue::from_static("\"abc\""),
+        );
+        headers.insert(
+            reqwest::header::LAST_MODIFIED,
+            reqwest::header::HeaderValue::from_static("Tue, 15 Dec 2020 10:00:00 GMT"),
+        );
+        let url = url::Url::parse("https://registry.npmjs.com/d3").unwrap();
+        let entry = Entry::new(&url, &headers, serde_json::json!({}));
+        let revalidation_headers = entry.get_revalidation_headers();
+        assert_eq!(
+            revalidation_headers[reqwest::header::IF_NONE_MATCH],
+            "\"abc\""
+        );
+        assert_eq!(
+            revalidation_headers[reqwest::header::IF_MODIFIED_SINCE],
+            "Tue, 15 Dec 2020 10:00:00 GMT"
+        );
+
+        let entry = get_entry(0);
+        assert!(entry.get_revalidation_headers().is_empty());
+    }
+
+    #[test]
+    fn test_insert_get_remove() -> Result<()> {
+        let cache = Cache::new(&testing::get_npm_config(""))?;
+        let registry_url = url::Url::parse("https://cache-test.example.com/")?;
+        let entry = get_entry(get_unix_time());
+
+        cache.insert(&registry_url, "@d3/core", PackumentFormat::Full, &entry)?;
+        let cached_entry = cache.get(&registry_url, "@d3/core", PackumentFormat::Full);
+        assert_eq!(cached_entry.unwrap().body, entry.body);
+        assert!(cache
+            .get(&registry_url, "@d3/core", PackumentFormat::Abbreviated)
+            .is_none());
+
+        cache.remove(&registry_url, "@d3/core", PackumentFormat::Full);
+        assert!(cache
+            .get(&registry_url, "@d3/core", PackumentFormat::Full)
+            .is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use anyhow::{Context, Result};
+
+use crate::archive;
+
+/// Number of bytes inspected when detecting binary files.
+static BINARY_DETECTION_LENGTH: usize = 8000;
+
+/// Package manifest sections which declare dependencies installed with the package.
+static DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "optionalDependencies", "peerDependencies"];
+
+/// Kind of change to a package file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FileChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Change to a single package file.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub path: std::path::PathBuf,
+    pub change: FileChange,
+    pub is_binary: bool,
+
+    /// Unified line diff. `None` for binary files.
+    pub unified_diff: Option<String>,
+}
+
+/// Change which deserves particular reviewer attention.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flag {
+    /// A binary file was added.
+    BinaryFileAdded { path: std::path::PathBuf },
+    /// A package.json script was added, removed or changed.
+    ScriptChanged {
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A dependency was added to the given package.json section.
+    DependencyAdded {
+        section: String,
+        name: String,
+        version_range: String,
+    },
+}
+
+impl std::fmt::Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BinaryFileAdded { path } => write!(f, "binary file added: {}", path.display()),
+            Self::ScriptChanged { name, old, new } => write!(
+                f,
+                "script {} changed: {} -> {}",
+                name,
+                old.as_deref().unwrap_or("(none)"),
+                new.as_deref().unwrap_or("(none)")
+            ),
+            Self::DependencyAdded {
+                section,
+                name,
+                version_range,
+            } => write!(
+                f,
+                "dependency added to {}: {}@{}",
+                section, name, version_range
+            ),
+        }
+    }
+}
+
+/// Differences between the published archives of two package versions.
+#[derive(Debug, Clone)]
+pub struct PackageDiff {
+    pub package_name: String,
+    pub old_version: String,
+    pub new_version: String,
+    pub files: Vec<FileDiff>,
+    pub flags: Vec<Flag>,
+}
+
+impl std::fmt::Display for PackageDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: {} -> {}",
+            self.package_name, self.old_version, self.new_version
+        )?;
+        if !self.flags.is_empty() {
+            writeln!(f, "\nFlags:")?;
+            for flag in &self.flags {
+                writeln!(f, "  {}", flag)?;
+            }
+        }
+        writeln!(f, "\nFiles:")?;
+        for file in &self.files {
+            let change = match file.change {
+                FileChange::Added => "A",
+                FileChange::Removed => "D",
+                FileChange::Modified => "M",
+            };
+            let binary = if file.is_binary { " (binary)" } else { "" };
+            writeln!(f, "  {} {}{}", change, file.path.display(), binary)?;
+        }
+        for unified_diff in self
+            .files
+            .iter()
+            .filter_map(|file| file.unified_diff.as_ref())
+        {
+            write!(f, "\n{}", unified_diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare package archive files. Returns changed files in path order.
+pub fn diff(old_files: &archive::Files, new_files: &archive::Files) -> Vec<FileDiff> {
+    let paths: std::collections::BTreeSet<_> = old_files.keys().chain(new_files.keys()).collect();
+
+    let mut file_diffs = Vec::new();
+    for path in paths {
+        let old_content = old_files.get(path);
+        let new_content = new_files.get(path);
+        let change = match (old_content, new_content) {
+            (Some(old_content), Some(new_content)) if old_content == new_content => continue,
+            (Some(_), Some(_)) => FileChange::Modified,
+            (None, Some(_)) => FileChange::Added,
+            (Some(_), None) => FileChange::Removed,
+            (None, None) => continue,
+        };
+        let old_text = get_text(old_content);
+        let new_text = get_text(new_content);
+        let is_binary = old_text.is_none() || new_text.is_none();
+
+        let unified_diff = match (old_text, new_text) {
+            (Some(old_text), Some(new_text)) => Some(
+                similar::TextDiff::from_lines(old_text, new_text)
+                    .unified_diff()
+                    .header(
+                        &get_diff_header_path("a", path, old_content.is_some()),
+                        &get_diff_header_path("b", path, new_content.is_some()),
+                    )
+                    .to_string(),
+            ),
+            _ => None,
+        };
+        file_diffs.push(FileDiff {
+            path: path.clone(),
+            change,
+            is_binary,
+            unified_diff,
+        });
+    }
+    file_diffs
+}
+
+/// Returns flags for added binary files, changed package.json scripts and new dependencies.
+pub fn get_flags(
+    old_files: &archive::Files,
+    new_files: &archive::Files,
+    file_diffs: &[FileDiff],
+) -> Result<Vec<Flag>> {
+    let mut flags: Vec<_> = file_diffs
+        .iter()
+        .filter(|file| file.change == FileChange::Added && file.is_binary)
+        .map(|file| Flag::BinaryFileAdded {
+            path: file.path.clone(),
+        })
+        .collect();
+
+    let manifest_path = std::path::Path::new("package.json");
+    let old_manifest = parse_manifest(old_files.get(manifest_path))?;
+    let new_manifest = parse_manifest(new_files.get(manifest_path))?;
+
+    let get_scripts = |manifest: &serde_json::Value| {
+        manifest["scripts"]
+            .as_object()
+            .map(|scripts| {
+                scripts
+                    .iter()
+                    .map(|(name, script)| (name.clone(), script.as_str().map(|v| v.to_string())))
+                    .collect::<std::collections::BTreeMap<_, _>>()
+            })
+            .unwrap_or_default()
+    };
+    let old_scripts = get_scripts(&old_manifest);
+    let new_scripts = get_scripts(&new_manifest);
+    let script_names: std::collections::BTreeSet<_> =
+        old_scripts.keys().chain(new_scripts.keys()).collect();
+    for name in script_names {
+        let old_script = old_scripts.get(name).cloned().flatten();
+        let new_script = new_scripts.get(name).cloned().flatten();
+        if old_script != new_script {
+            flags.push(Flag::ScriptChanged {
+                name: name.clone(),
+                old: old_script,
+                new: new_script,
+            });
+        }
+    }
+
+    for section in DEPENDENCY_SECTIONS {
+        let new_dependencies = match new_manifest[section].as_object() {
+            Some(v) => v,
+            None => continue,
+        };
+        for (name, version_range) in new_dependencies {
+            if old_manifest[section][name].is_null() {
+                flags.push(Flag::DependencyAdded {
+                    section: section.to_string(),
+                    name: name.clone(),
+                    version_range: version_range.as_str().unwrap_or_default().to_string(),
+                });
+            }
+        }
+    }
+    Ok(flags)
+}
+
+/// Returns file content as text, or `None` if the file is binary.
+///
+/// Files containing null bytes or invalid UTF-8 are treated as binary. Missing files are
+/// empty text.
+fn get_text(content: Option<&Vec<u8>>) -> Option<&str> {
+    let content = match content {
+        Some(v) => v,
+        None => return Some(""),
+    };
+    let prefix_length = std::cmp::min(content.len(), BINARY_DETECTION_LENGTH);
+    if content[..prefix_length].contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// Returns a unified diff header path. Missing files are shown as `/dev/null`.
+fn get_diff_header_path(prefix: &str, path: &std::path::Path, exists: bool) -> String {
+    if exists {
+        format!("{}/{}", prefix, path.display())
+    } else {
+        "/dev/null".to_string()
+    }
+}
+
+fn parse_manifest(content: Option<&Vec<u8>>) -> Result<serde_json::Value> {
+    match content {
+        Some(content) => {
+            serde_json::from_slice(content).context("Failed to parse package archive package.json.")
+        }
+        None => Ok(serde_json::Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_files(files: &[(&str, &[u8])]) -> archive::Files {
+        files
+            .iter()
+            .map(|(path, content)| (std::path::PathBuf::from(path), content.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old_files = get_files(&[
+            ("README.md", b"# d3\n"),
+            ("index.js", b"module.exports = 1;\n"),
+            ("removed.js", b"removed\n"),
+            ("logo.png", b"text\n"),
+        ]);
+        let new_files = get_files(&[
+            ("README.md", b"# d3\n"),
+            ("index.js", b"module.exports = 2;\n"),
+            ("added.node", b"\x7fELF\x00\x01"),
+            ("logo.png", b"\x89PNG\x00"),
+        ]);
+        let file_diffs = diff(&old_files, &new_files);
+        let changes: Vec<_> = file_diffs
+            .iter()
+            .map(|file| (file.path.to_str().unwrap(), file.change, file.is_binary))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("added.node", FileChange::Added, true),
+                ("index.js", FileChange::Modified, false),
+                ("logo.png", FileChange::Modified, true),
+                ("removed.js", FileChange::Removed, false),
+            ]
+        );
+
+        assert_eq!(
+            file_diffs[1].unified_diff.as_deref(),
+            Some(
+                "--- a/index.js\n+++ b/index.js\n@@ -1 +1 @@\n\
+                 -module.exports = 1;\n+module.exports = 2;\n"
+            )
+        );
+        assert!(file_diffs[2].unified_diff.is_none());
+        assert!(file_diffs[3]
+            .unified_diff
+            .as_deref()
+            .unwrap()
+            .starts_with("--- a/removed.js\n+++ /dev/null\n"));
+    }
+
+    #[test]
+    fn test_get_flags() -> Result<()> {
+        let old_files = get_files(&[(
+            "package.json",
+            br#"{
+                "scripts": {"test": "jest", "build": "tsc"},
+                "dependencies": {"d3-array": "^2.0.0"}
+            }"#,
+        )]);
+        let new_files = get_files(&[
+            (
+                "package.json",
+                br#"{
+                    "scripts": {"test": "jest", "build": "tsc && node build.js", "postinstall": "node install.js"},
+                    "dependencies": {"d3-array": "^2.12.0", "d3-color": "^2.0.0"},
+                    "optionalDependencies": {"fsevents": "~2.3.1"},
+                    "devDependencies": {"jest": "^26.6.0"}
+                }"#,
+            ),
+            ("prebuilt/addon.node", b"\x7fELF\x00\x01"),
+        ]);
+        let file_diffs = diff(&old_files, &new_files);
+        let flags = get_flags(&old_files, &new_files, &file_diffs)?;
+        assert_eq!(
+            flags,
+            vec![
+                Flag::BinaryFileAdded {
+                    path: std::path::PathBuf::from("prebuilt/addon.node"),
+                },
+                Flag::ScriptChanged {
+                    name: "build".to_string(),
+                    old: Some("tsc".to_string()),
+                    new: Some("tsc && node build.js".to_string()),
+                },
+                Flag::ScriptChanged {
+                    name: "postinstall".to_string(),
+                    old: None,
+                    new: Some("node install.js".to_string()),
+                },
+                Flag::DependencyAdded {
+                    section: "dependencies".to_string(),
+                    name: "d3-color".to_string(),
+                    version_range: "^2.0.0".to_string(),
+                },
+                Flag::DependencyAdded {
+                    section: "optionalDependencies".to_string(),
+                    name: "fsevents".to_string(),
+                    version_range: "~2.3.1".to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_flags_text_to_binary_and_missing_manifest() -> Result<()> {
+        // A text file which becomes binary is modified rather than added.
+        let old_files = get_files(&[("index.js", b"module.exports = 1;\n")]);
+        let new_files = get_files(&[
+            ("index.js", b"\x00asm\x01"),
+            (
+                "package.json",
+                br#"{"scripts": {"install": "node-gyp rebuild"}}"#,
+            ),
+        ]);
+        let file_diffs = diff(&old_files, &new_files);
+        assert_eq!(file_diffs[0].change, FileChange::Modified);
+        assert!(file_diffs[0].is_binary);
+
+        // The old archive has no package.json.
+        let flags = get_flags(&old_files, &new_files, &file_diffs)?;
+        assert_eq!(
+            flags,
+            vec![Flag::ScriptChanged {
+                name: "install".to_string(),
+                old: None,
+                new: Some("node-gyp rebuild".to_string()),
+            }]
+        );
+
+        // Neither archive has a package.json.
+        let new_files = get_files(&[("index.js", b"module.exports = 2;\n")]);
+        let file_diffs = diff(&old_files, &new_files);
+        assert!(get_flags(&old_files, &new_files, &file_diffs)?.is_empty());
+
+        // An invalid package.json is an error.
+        let new_files = get_files(&[("package.json", b"{")]);
+        let file_diffs = diff(&old_files, &new_files);
+        assert!(get_flags(&old_files, &new_files, &file_diffs).is_err());
+        Ok(())
+    }
+}
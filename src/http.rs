@@ -0,0 +1,383 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::HashMap;
+
+use crate::npmrc;
+
+/// Default maximum number of concurrent connections per host, as used by npm.
+static DEFAULT_MAX_SOCKETS: usize = 15;
+
+/// Default request timeout in milliseconds, as used by npm.
+static DEFAULT_FETCH_TIMEOUT: u64 = 5 * 60 * 1000;
+
+/// Maximum time allowed to establish a connection.
+static CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Delimiter which ends each certificate in a PEM encoded certificate bundle.
+static PEM_CERTIFICATE_DELIMITER: &str = "-----END CERTIFICATE-----";
+
+/// Request retry settings, configured with npm's `fetch-retries`, `fetch-retry-factor`,
+/// `fetch-retry-mintimeout` and `fetch-retry-maxtimeout` (milliseconds) options.
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    retries: u32,
+    factor: u32,
+    min_timeout: std::time::Duration,
+    max_timeout: std::time::Duration,
+}
+
+impl RetryPolicy {
+    fn new(npm_config: &npmrc::Config) -> Result<Self> {
+        Ok(Self {
+            retries: get_config_number(npm_config, "fetch-retries", 2)?,
+            factor: get_config_number(npm_config, "fetch-retry-factor", 10)?,
+            min_timeout: std::time::Duration::from_millis(get_config_number(
+                npm_config,
+                "fetch-retry-mintimeout",
+                10_000,
+            )?),
+            max_timeout: std::time::Duration::from_millis(get_config_number(
+                npm_config,
+                "fetch-retry-maxtimeout",
+                60_000,
+            )?),
+        })
+    }
+
+    /// Returns the exponential backoff delay before the given retry attempt (from zero).
+    fn get_delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = self.factor.saturating_pow(attempt);
+        std::cmp::min(self.min_timeout * factor, self.max_timeout)
+    }
+}
+
+/// Parse a numeric configuration value, or return the default if it is not set.
+fn get_config_number<T: std::str::FromStr>(
+    npm_config: &npmrc::Config,
+    key: &str,
+    default: T,
+) -> Result<T> {
+    match npm_config.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format_err!("Invalid npm config {} value: {}", key, value)),
+        None => Ok(default),
+    }
+}
+
+/// Returns a proxy environment variable value. Lower case names take precedence.
+fn get_proxy_environment_variable(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_uppercase()))
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// Proxy settings, configured with npm's `https-proxy`, `proxy` and `noproxy` options.
+///
+/// Falls back to the `HTTPS_PROXY`, `HTTP_PROXY`, `PROXY` and `NO_PROXY` environment
+/// variables, following npm.
+#[derive(Debug, Clone, Default)]
+struct ProxyConfig {
+    proxy: Option<url::Url>,
+    https_environment_proxy: Option<url::Url>,
+    http_environment_proxy: Option<url::Url>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    fn new(npm_config: &npmrc::Config) -> Result<Self> {
+        let parse = |value: String| -> Result<url::Url> {
+            url::Url::parse(&value).context(format!("Invalid proxy URL: {}", value))
+        };
+        let proxy = npm_config
+            .get("https-proxy")
+            .or_else(|| npm_config.get("proxy"))
+            .map(|v| parse(v.to_string()))
+            .transpose()?;
+        let https_environment_proxy = get_proxy_environment_variable("https_proxy")
+            .map(parse)
+            .transpose()?;
+        let http_environment_proxy = get_proxy_environment_variable("https_proxy")
+            .or_else(|| get_proxy_environment_variable("http_proxy"))
+            .or_else(|| get_proxy_environment_variable("proxy"))
+            .map(parse)
+            .transpose()?;
+
+        let no_proxy = npm_config
+            .get("noproxy")
+            .map(|v| v.to_string())
+            .or_else(|| get_proxy_environment_variable("no_proxy"))
+            .unwrap_or_default()
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
+
+        Ok(Self {
+            proxy,
+            https_environment_proxy,
+            http_environment_proxy,
+            no_proxy,
+        })
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.proxy.is_some()
+            || self.https_environment_proxy.is_some()
+            || self.http_environment_proxy.is_some()
+    }
+
+    /// Returns true if the URL host matches a `noproxy` domain, or is a subdomain of it.
+    fn is_no_proxy(&self, url: &url::Url) -> bool {
+        let host_segments: Vec<_> = url.host_str().unwrap_or_default().rsplit('.').collect();
+        self.no_proxy.iter().any(|domain| {
+            let domain_segments: Vec<_> = domain.rsplit('.').filter(|v| !v.is_empty()).collect();
+            !domain_segments.is_empty()
+                && domain_segments
+                    .iter()
+                    .enumerate()
+                    .all(|(index, segment)| host_segments.get(index) == Some(segment))
+        })
+    }
+
+    /// Returns the proxy URL to use for a request URL, if any.
+    fn get_proxy(&self, url: &url::Url) -> Option<url::Url> {
+        let proxy = self.proxy.as_ref().or(match url.scheme() {
+            "https" => self.https_environment_proxy.as_ref(),
+            _ => self.http_environment_proxy.as_ref(),
+        })?;
+        if self.is_no_proxy(url) {
+            return None;
+        }
+        Some(proxy.clone())
+    }
+}
+
+/// Returns additional trusted certificate authorities, configured with npm's `ca`
+/// (PEM encoded, with escaped newlines) and `cafile` (path to PEM file) options.
+fn get_root_certificates(npm_config: &npmrc::Config) -> Result<Vec<reqwest::Certificate>> {
+    let mut bundles = Vec::new();
+    if let Some(ca) = npm_config.get("ca") {
+        bundles.push(ca.replace("\\n", "\n"));
+    }
+    if let Some(cafile) = npm_config.get("cafile") {
+        bundles.push(
+            std::fs::read_to_string(cafile)
+                .context(format!("Failed to read npm config cafile: {}", cafile))?,
+        );
+    }
+
+    let mut certificates = Vec::new();
+    for bundle in bundles {
+        let bundle = bundle.replace("\r\n", "\n");
+        for section in bundle
+            .split(PEM_CERTIFICATE_DELIMITER)
+            .filter(|v| !v.trim().is_empty())
+        {
+            let pem = format!("{}{}", section.trim_start(), PEM_CERTIFICATE_DELIMITER);
+            certificates.push(
+                reqwest::Certificate::from_pem(pem.as_bytes())
+                    .context("Failed to parse certificate authority from npm config.")?,
+            );
+        }
+    }
+    Ok(certificates)
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    in_flight: usize,
+    paused_until: Option<std::time::Instant>,
+}
+
+/// Limits the number of concurrent requests per host.
+///
+/// Requests to a host are paused when the host responds with `429 Too Many Requests`.
+#[derive(Debug)]
+struct HostLimiter {
+    max_in_flight: usize,
+    hosts: std::sync::Mutex<HashMap<String, HostState>>,
+    condvar: std::sync::Condvar,
+}
+
+impl HostLimiter {
+    fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            hosts: std::sync::Mutex::new(HashMap::new()),
+            condvar: std::sync::Condvar::new(),
+        }
+    }
+
+    /// Wait until a request to the host is permitted.
+    fn acquire(&self, host: &str) {
+        let mut hosts = self.hosts.lock().expect("host limiter lock");
+        loop {
+            let state = hosts.entry(host.to_string()).or_default();
+            let now = std::time::Instant::now();
+            match state.paused_until {
+                Some(paused_until) if paused_until > now => {
+                    hosts = self
+                        .condvar
+                        .wait_timeout(hosts, paused_until - now)
+                        .expect("host limiter lock")
+                        .0;
+                }
+                _ if state.in_flight >= self.max_in_flight => {
+                    hosts = self.condvar.wait(hosts).expect("host limiter lock");
+                }
+                _ => {
+                    state.in_flight += 1;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Mark a request to the host as complete.
+    fn release(&self, host: &str) {
+        let mut hosts = self.hosts.lock().expect("host limiter lock");
+        if let Some(state) = hosts.get_mut(host) {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.condvar.notify_all();
+    }
+
+    /// Pause all requests to the host for the given duration.
+    fn pause(&self, host: &str, duration: std::time::Duration) {
+        let mut hosts = self.hosts.lock().expect("host limiter lock");
+        let state = hosts.entry(host.to_string()).or_default();
+        let paused_until = std::time::Instant::now() + duration;
+        if state.paused_until.map(|v| v < paused_until).unwrap_or(true) {
+            state.paused_until = Some(paused_until);
+        }
+    }
+}
+
+/// Returns true if a request which received the response status should be retried.
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Returns the delay requested by a response `Retry-After` header given in seconds.
+fn get_retry_after(response: &reqwest::blocking::Response) -> Option<std::time::Duration> {
+    let seconds = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+/// HTTP client shared by all registry requests.
+///
+/// Connections are pooled and the number of concurrent requests per host is limited
+/// by npm's `maxsockets` option. Failed requests are retried with exponential backoff.
+///
+/// Honors npm's `fetch-timeout` (milliseconds, zero disables), proxy, `ca`, `cafile`
+/// and `strict-ssl` options. Configured certificate authorities are trusted in
+/// addition to the system certificate authorities.
+#[derive(Debug, Clone)]
+pub struct Client {
+    client: reqwest::blocking::Client,
+    retry_policy: RetryPolicy,
+    host_limiter: std::sync::Arc<HostLimiter>,
+    max_sockets: usize,
+}
+
+impl Client {
+    pub fn new(npm_config: &npmrc::Config) -> Result<Self> {
+        let max_sockets = get_config_number(npm_config, "maxsockets", DEFAULT_MAX_SOCKETS)?.max(1);
+        let timeout = match get_config_number(npm_config, "fetch-timeout", DEFAULT_FETCH_TIMEOUT)? {
+            0 => None,
+            timeout => Some(std::time::Duration::from_millis(timeout)),
+        };
+
+        let mut builder = reqwest::blocking::Client::builder()
+            .pool_max_idle_per_host(max_sockets)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(timeout);
+
+        let proxy_config = ProxyConfig::new(npm_config)?;
+        builder = if proxy_config.is_enabled() {
+            builder.proxy(reqwest::Proxy::custom(move |url| {
+                proxy_config.get_proxy(url)
+            }))
+        } else {
+            builder.no_proxy()
+        };
+
+        for certificate in get_root_certificates(npm_config)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+        if npm_config.get("strict-ssl") == Some("false") {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        let client = builder.build().context("Failed to create HTTP client.")?;
+
+        Ok(Self {
+            client,
+            retry_policy: RetryPolicy::new(npm_config)?,
+            host_limiter: std::sync::Arc::new(HostLimiter::new(max_sockets)),
+            max_sockets,
+        })
+    }
+
+    /// Returns the maximum number of concurrent requests per host.
+    pub fn get_max_sockets(&self) -> usize {
+        self.max_sockets
+    }
+
+    /// Send a GET request.
+    ///
+    /// Requests which fail to connect, time out, or receive a `429 Too Many Requests`
+    /// or server error response are retried. The final response is returned regardless
+    /// of its status.
+    pub fn get(
+        &self,
+        url: &url::Url,
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<reqwest::blocking::Response> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut attempt = 0;
+        loop {
+            self.host_limiter.acquire(&host);
+            let result = self
+                .client
+                .get(url.as_str())
+                .headers(headers.clone())
+                .send();
+            self.host_limiter.release(&host);
+
+            let retry_delay = match &result {
+                Ok(response) if is_retryable(response.status()) => Some(
+                    get_retry_after(response)
+                        .map(|v| std::cmp::min(v, self.retry_policy.max_timeout))
+                        .unwrap_or_else(|| self.retry_policy.get_delay(attempt)),
+                ),
+                Err(error) if error.is_connect() || error.is_timeout() => {
+                    Some(self.retry_policy.get_delay(attempt))
+                }
+                _ => None,
+            };
+            let retry_delay = match retry_delay {
+                Some(v) if attempt < self.retry_policy.retries => v,
+                _ => return result.context(format!("HTTP request failed: {}", url)),
+            };
+
+            let is_rate_limited = result
+                .map(|response| response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS)
+                .unwrap_or_default();
+            if is_rate_limited {
+                self.host_limiter.pause(&host, retry_delay);
+            } else {
+                std::thread::sleep(retry_delay);
+            }
+            attempt += 1;
+        }
+    }
+}
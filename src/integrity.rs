@@ -0,0 +1,276 @@
+use anyhow::{format_err, Result};
+use sha2::Digest;
+
+/// Hash algorithms supported in integrity metadata, from weakest to strongest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "sha384" => Some(Self::Sha384),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1 => sha1::Sha1::digest(data).to_vec(),
+            Self::Sha256 => sha2::Sha256::digest(data).to_vec(),
+            Self::Sha384 => sha2::Sha384::digest(data).to_vec(),
+            Self::Sha512 => sha2::Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// Single hash of integrity metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hash {
+    pub algorithm: Algorithm,
+    pub digest: Vec<u8>,
+}
+
+impl std::fmt::Display for Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.algorithm.name(),
+            base64::encode(&self.digest)
+        )
+    }
+}
+
+/// Package content integrity metadata.
+///
+/// Combines Subresource Integrity (SRI) hashes, such as `sha512-<base64 digest>`,
+/// with legacy hex encoded sha1 `shasum` values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Integrity {
+    hashes: Vec<Hash>,
+}
+
+impl Integrity {
+    /// Parse a Subresource Integrity string. Hashes using unsupported algorithms are ignored.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut hashes = Vec::new();
+        for hash in value.split_whitespace() {
+            // Options may follow the digest: `sha512-<digest>?<options>`.
+            let hash = hash.split('?').next().unwrap_or_default();
+            let (algorithm, digest) = match hash.split_once('-') {
+                Some(v) => v,
+                None => continue,
+            };
+            let algorithm = match Algorithm::parse(algorithm) {
+                Some(v) => v,
+                None => continue,
+            };
+            let digest = base64::decode(digest)
+                .map_err(|_| format_err!("Invalid integrity hash: {}", hash))?;
+            hashes.push(Hash { algorithm, digest });
+        }
+        Ok(Self { hashes })
+    }
+
+    /// Add a hex encoded sha1 digest, as found in npm `shasum` fields.
+    pub fn add_shasum(&mut self, shasum: &str) -> Result<()> {
+        let shasum = shasum.trim();
+        let digest = decode_hex(shasum)
+            .filter(|digest| digest.len() == 20)
+            .ok_or(format_err!("Invalid shasum: {}", shasum))?;
+        self.hashes.push(Hash {
+            algorithm: Algorithm::Sha1,
+            digest,
+        });
+        Ok(())
+    }
+
+    /// Returns integrity metadata given SRI `integrity` and hex sha1 `shasum` values.
+    pub fn from_values(integrity: Option<&str>, shasum: Option<&str>) -> Result<Self> {
+        let mut result = match integrity {
+            Some(integrity) => Self::parse(integrity)?,
+            None => Self::default(),
+        };
+        if let Some(shasum) = shasum {
+            result.add_shasum(shasum)?;
+        }
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Compare against other integrity metadata using the strongest algorithm both provide.
+    ///
+    /// Returns true if any hashes of that algorithm match, or `None` if the two share
+    /// no algorithm.
+    pub fn matches(&self, other: &Self) -> Option<bool> {
+        let algorithm = self
+            .hashes
+            .iter()
+            .map(|hash| hash.algorithm)
+            .filter(|algorithm| other.hashes.iter().any(|hash| hash.algorithm == *algorithm))
+            .max()?;
+        Some(self.hashes.iter().any(|hash| {
+            hash.algorithm == algorithm && other.hashes.iter().any(|other_hash| other_hash == hash)
+        }))
+    }
+
+    /// Verify data against every hash algorithm present.
+    ///
+    /// For each algorithm, the data must match at least one of the given hashes.
+    pub fn verify(&self, data: &[u8]) -> Result<()> {
+        if self.is_empty() {
+            return Err(format_err!(
+                "No supported integrity hashes to verify against."
+            ));
+        }
+
+        let mut algorithms: Vec<_> = self.hashes.iter().map(|hash| hash.algorithm).collect();
+        algorithms.sort();
+        algorithms.dedup();
+        for algorithm in algorithms.into_iter().rev() {
+            let digest = algorithm.digest(data);
+            let expected_hashes: Vec<_> = self
+                .hashes
+                .iter()
+                .filter(|hash| hash.algorithm == algorithm)
+                .collect();
+            if !expected_hashes.iter().any(|hash| hash.digest == digest) {
+                let expected: Vec<_> = expected_hashes
+                    .iter()
+                    .map(|hash| hash.to_string())
+                    .collect();
+                return Err(format_err!(
+                    "Integrity check failed.\nExpected: {}\nFound: {}",
+                    expected.join(" "),
+                    Hash { algorithm, digest }
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Integrity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hashes: Vec<_> = self.hashes.iter().map(|hash| hash.to_string()).collect();
+        write!(f, "{}", hashes.join(" "))
+    }
+}
+
+/// Decode a hex encoded digest.
+pub fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    (0..value.len())
+        .step_by(2)
+        .map(|index| {
+            value
+                .get(index..index + 2)
+                .and_then(|v| u8::from_str_radix(v, 16).ok())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_hash(algorithm: Algorithm, data: &[u8]) -> String {
+        Hash {
+            algorithm,
+            digest: algorithm.digest(data),
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let sha512 = get_hash(Algorithm::Sha512, b"archive");
+        let integrity = Integrity::parse(&format!("md5-AAAA {}?options", sha512))?;
+        assert_eq!(integrity.to_string(), sha512);
+        assert!(Integrity::parse("sha512-not base64!").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let sha512 = get_hash(Algorithm::Sha512, b"archive");
+        let sha1 = get_hash(Algorithm::Sha1, b"archive");
+        let other_sha512 = get_hash(Algorithm::Sha512, b"other");
+        let other_sha1 = get_hash(Algorithm::Sha1, b"other");
+        let registry_integrity = Integrity::parse(&format!("{} {}", sha512, sha1))?;
+
+        let cases = vec![
+            (sha512.clone(), Some(true)),
+            (sha1.clone(), Some(true)),
+            (other_sha512.clone(), Some(false)),
+            // The strongest shared algorithm decides.
+            (format!("{} {}", other_sha512, sha1), Some(false)),
+            (format!("{} {}", sha512, other_sha1), Some(true)),
+            (get_hash(Algorithm::Sha256, b"archive"), None),
+            (String::new(), None),
+        ];
+        for (lockfile_integrity, expected) in cases {
+            let lockfile_integrity = Integrity::parse(&lockfile_integrity)?;
+            assert_eq!(
+                lockfile_integrity.matches(&registry_integrity),
+                expected,
+                "{}",
+                lockfile_integrity
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_values_shasum() -> Result<()> {
+        let shasum: String = Algorithm::Sha1
+            .digest(b"archive")
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let integrity = Integrity::from_values(None, Some(&shasum))?;
+        assert_eq!(integrity.to_string(), get_hash(Algorithm::Sha1, b"archive"));
+        assert!(Integrity::from_values(None, Some("abc")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let integrity = Integrity::parse(&format!(
+            "{} {}",
+            get_hash(Algorithm::Sha512, b"archive"),
+            get_hash(Algorithm::Sha1, b"archive")
+        ))?;
+        integrity.verify(b"archive")?;
+        assert!(integrity.verify(b"tampered").is_err());
+
+        // Every algorithm present must match.
+        let integrity = Integrity::parse(&format!(
+            "{} {}",
+            get_hash(Algorithm::Sha512, b"archive"),
+            get_hash(Algorithm::Sha1, b"other")
+        ))?;
+        assert!(integrity.verify(b"archive").is_err());
+        assert!(Integrity::default().verify(b"archive").is_err());
+        Ok(())
+    }
+}
@@ -2,7 +2,29 @@ use anyhow::{format_err, Context, Result};
 use std::io::Read;
 use strum::IntoEnumIterator;
 
+mod archive;
+mod audit;
+mod bun;
+mod cacache;
+mod cache;
+mod diff;
+mod http;
+mod integrity;
 mod npm;
+mod npmrc;
+mod package_json;
+mod package_name;
+mod pnpm;
+mod resolver;
+mod signatures;
+#[cfg(test)]
+mod testing;
+mod version_range;
+mod yarn;
+
+pub use audit::{Finding as AuditFinding, Issue as AuditIssue};
+pub use diff::{FileChange, FileDiff, Flag as DiffFlag, PackageDiff};
+pub use signatures::SignatureStatus;
 
 #[derive(Clone, Debug)]
 pub struct JsExtension {
@@ -10,17 +32,503 @@ pub struct JsExtension {
     registry_host_names_: Vec<String>,
     root_url_: url::Url,
     registry_human_url_template_: String,
+
+    /// npm configuration, or the error encountered loading it.
+    npm_config_: std::result::Result<npmrc::Config, String>,
+    /// HTTP client, or the error encountered creating it from the npm configuration.
+    http_client_: std::result::Result<http::Client, String>,
+
+    /// Registry signing keys by registry URL. `None` if the registry publishes no keys.
+    registry_keys_: std::sync::Arc<
+        std::sync::Mutex<std::collections::HashMap<url::Url, Option<Vec<signatures::RegistryKey>>>>,
+    >,
 }
 
 impl vouch_lib::extension::FromLib for JsExtension {
     fn new() -> Self {
+        let working_directory = std::env::current_dir().unwrap_or_default();
+        Self::from_npm_config(npmrc::Config::load(&working_directory))
+    }
+}
+
+impl JsExtension {
+    /// Returns an extension which uses the given npm configuration.
+    ///
+    /// Configuration errors are stored, and returned by the first method which
+    /// requires the configuration.
+    fn from_npm_config(npm_config: Result<npmrc::Config>) -> Self {
+        let npm_config = npm_config
+            .and_then(|npm_config| {
+                let registry_host_names = npm_config
+                    .get_registry_urls()?
+                    .iter()
+                    .map(npmrc::get_registry_host_name)
+                    .collect::<Result<Vec<_>>>()?;
+                let http_client = http::Client::new(&npm_config)?;
+                Ok((npm_config, registry_host_names, http_client))
+            })
+            .context("Failed to load npm configuration");
+        let (npm_config, registry_host_names, http_client) = match npm_config {
+            Ok((npm_config, registry_host_names, http_client)) => {
+                (Ok(npm_config), registry_host_names, Ok(http_client))
+            }
+            Err(error) => {
+                let error = format!("{:#}", error);
+                (Err(error.clone()), Vec::new(), Err(error))
+            }
+        };
+
         Self {
             name_: "js".to_string(),
-            registry_host_names_: vec!["npmjs.com".to_owned()],
+            registry_host_names_: registry_host_names,
             root_url_: url::Url::parse("https://www.npmjs.com").unwrap(),
             registry_human_url_template_:
                 "https://www.npmjs.com/package/{{package_name}}/v/{{package_version}}".to_string(),
+            npm_config_: npm_config,
+            http_client_: http_client,
+            registry_keys_: Default::default(),
+        }
+    }
+
+    /// Returns the npm configuration, or the error encountered loading it.
+    fn npm_config(&self) -> Result<&npmrc::Config> {
+        self.npm_config_
+            .as_ref()
+            .map_err(|error| format_err!("{}", error))
+    }
+
+    /// Returns the HTTP client, or the error encountered creating it.
+    fn http_client(&self) -> Result<&http::Client> {
+        self.http_client_
+            .as_ref()
+            .map_err(|error| format_err!("{}", error))
+    }
+
+    /// Returns package metadata from each configured registry for many packages.
+    ///
+    /// Registry entries are fetched concurrently and only once per package name,
+    /// regardless of the number of requested versions. Requested versions are resolved
+    /// and verified by the same concurrent workers. Returns one result per given
+    /// (package name, package version) pair, in the given order.
+    pub fn registries_packages_metadata(
+        &self,
+        packages: &[(&str, Option<&str>)],
+    ) -> Vec<Result<PackageMetadata>> {
+        let mut package_versions = std::collections::BTreeMap::<_, Vec<_>>::new();
+        for (package_name, package_version) in packages {
+            package_versions
+                .entry(*package_name)
+                .or_default()
+                .push(*package_version);
+        }
+
+        let all_package_metadata =
+            self.fetch_concurrently(package_versions.keys().copied().collect(), |package_name| {
+                let registry_entries = self.get_registry_entries(package_name)?;
+                Ok(package_versions[package_name]
+                    .iter()
+                    .map(|package_version| {
+                        let package_metadata = get_registries_package_metadata(
+                            self,
+                            package_name,
+                            package_version,
+                            &registry_entries,
+                        );
+                        (*package_version, package_metadata)
+                    })
+                    .collect::<std::collections::HashMap<_, _>>())
+            });
+
+        packages
+            .iter()
+            .map(
+                |(package_name, package_version)| match &all_package_metadata[package_name] {
+                    Ok(package_metadata) => match &package_metadata[package_version] {
+                        Ok(package_metadata) => Ok(package_metadata.clone()),
+                        Err(error) => Err(format_err!("{:#}", error)),
+                    },
+                    Err(error) => Err(format_err!("{:#}", error)),
+                },
+            )
+            .collect()
+    }
+
+    /// Returns dependencies which are excluded from file defined dependencies.
+    ///
+    /// Packages resolved through protocols such as `workspace:`, `patch:`, `git` or `file:`
+    /// are not fetched from a package registry, so they can not be reviewed as registry
+    /// packages. Dev dependencies are included given the `--dev` argument.
+    pub fn identify_excluded_dependencies(
+        &self,
+        working_directory: &std::path::PathBuf,
+        extension_args: &[String],
+    ) -> Result<Vec<ExcludedDependencies>> {
+        let include_dev_dependencies = extension_args.iter().any(|v| v == "--dev");
+
+        let mut all_excluded_dependencies = Vec::new();
+        for (path, dependencies) in
+            read_dependency_files(working_directory, include_dev_dependencies)?
+        {
+            for (protocol, dependencies) in dependencies.excluded_dependencies {
+                all_excluded_dependencies.push(ExcludedDependencies {
+                    path: path.clone(),
+                    protocol,
+                    dependencies: dependencies.into_iter().collect(),
+                });
+            }
+        }
+        Ok(all_excluded_dependencies)
+    }
+
+    /// Check npm lockfile `integrity` and `resolved` values against the registry.
+    ///
+    /// Each registry package version in package-lock.json or npm-shrinkwrap.json is
+    /// compared with the primary registry entry for the same version. Returns mismatched
+    /// integrity hashes and archive URLs, archive URLs which are not served by a configured
+    /// registry, and entries without integrity hashes.
+    pub fn audit_dependency_files(
+        &self,
+        working_directory: &std::path::PathBuf,
+        include_dev_dependencies: bool,
+    ) -> Result<Vec<AuditIssue>> {
+        let npm_config = self.npm_config()?;
+        let dependency_files = identify_dependency_files(working_directory).unwrap_or_default();
+
+        let mut packages = Vec::new();
+        for dependency_file in dependency_files {
+            if dependency_file.r#type != DependencyFileType::Npm
+                && dependency_file.r#type != DependencyFileType::NpmShrinkwrap
+            {
+                continue;
+            }
+            for entry in npm::get_lockfile_entries(&dependency_file.path, include_dev_dependencies)?
+            {
+                if let Some(package) = audit::Package::from_lockfile_entry(entry) {
+                    packages.push((dependency_file.path.clone(), package));
+                }
+            }
+        }
+
+        let all_registry_entries = self.fetch_concurrently(
+            packages
+                .iter()
+                .map(|(_, package)| package.name.as_str())
+                .collect(),
+            |package_name| -> Result<(url::Url, Option<serde_json::Value>)> {
+                let registry_url = npm_config.get_registry_url(package_name)?;
+                let entry_json = find_registry_entry_json(
+                    npm_config,
+                    self.http_client()?,
+                    &registry_url,
+                    package_name,
+                    PackumentFormat::Abbreviated,
+                )
+                .context(format!("Failed to query registry: {}", registry_url))?;
+                Ok((registry_url, entry_json))
+            },
+        );
+
+        let registry_urls = npm_config.get_registry_urls()?;
+        let mut issues = Vec::new();
+        for (path, package) in &packages {
+            let (registry_url, entry_json) = match &all_registry_entries[package.name.as_str()] {
+                Ok(v) => v,
+                Err(error) => return Err(format_err!("{:#}", error)),
+            };
+            let findings =
+                audit::check_package(package, registry_url, entry_json.as_ref(), &registry_urls)?;
+            for finding in findings {
+                issues.push(AuditIssue {
+                    path: path.clone(),
+                    package_name: package.name.clone(),
+                    package_version: package.version.clone(),
+                    finding,
+                });
+            }
         }
+        Ok(issues)
+    }
+
+    /// Returns the archive (tarball) location and expected integrity hashes of a package
+    /// version from the primary registry.
+    pub fn get_package_archive(
+        &self,
+        package_name: &str,
+        package_version: &Option<&str>,
+    ) -> Result<PackageArchive> {
+        let npm_config = self.npm_config()?;
+        let registry_url = npm_config.get_registry_url(package_name)?;
+        let entry_json = get_registry_entry_json(
+            npm_config,
+            self.http_client()?,
+            &registry_url,
+            package_name,
+            PackumentFormat::Abbreviated,
+        )?;
+        let package_version = resolve_package_version(self, &entry_json, package_version)?;
+        get_package_archive(&registry_url, &entry_json, &package_version)
+    }
+
+    /// Verify the registry signatures of a package version from the primary registry.
+    ///
+    /// Invalid signatures, and signatures made with unknown or expired keys, are errors.
+    pub fn verify_package(
+        &self,
+        package_name: &str,
+        package_version: &Option<&str>,
+    ) -> Result<PackageVerification> {
+        let npm_config = self.npm_config()?;
+        let registry_url = npm_config.get_registry_url(package_name)?;
+        let entry_json = get_registry_entry_json(
+            npm_config,
+            self.http_client()?,
+            &registry_url,
+            package_name,
+            PackumentFormat::Abbreviated,
+        )?;
+        let package_version = resolve_package_version(self, &entry_json, package_version)?;
+        verify_package_version(
+            self,
+            &registry_url,
+            &entry_json,
+            package_name,
+            &package_version,
+        )
+    }
+
+    /// Download a package archive and verify it against the expected integrity hashes.
+    ///
+    /// Returns the archive content. Archives which do not match, or which have no
+    /// integrity hashes to verify against, are rejected. In offline mode, the archive
+    /// is read from npm's cache.
+    pub fn download_package_archive(&self, archive: &PackageArchive) -> Result<Vec<u8>> {
+        let npm_config = self.npm_config()?;
+        let integrity = archive.get_integrity()?;
+        if integrity.is_empty() {
+            return Err(format_err!(
+                "Registry provides no integrity hashes for package archive: {}",
+                archive.url
+            ));
+        }
+
+        let content = if npm_config.is_offline() {
+            let integrity = archive.integrity.as_deref().unwrap_or_default();
+            cacache::get_content(npm_config, integrity)?.ok_or(format_err!(
+                "Package archive is not available offline: it was not found in npm's cache: {}",
+                archive.url
+            ))?
+        } else {
+            let mut response = send_registry_request(
+                npm_config,
+                self.http_client()?,
+                &archive.registry_url,
+                &archive.url,
+                reqwest::header::HeaderMap::new(),
+            )?
+            .ok_or(format_err!(
+                "Failed to find package archive: {}",
+                archive.url
+            ))?;
+            let mut content = Vec::new();
+            response.read_to_end(&mut content)?;
+            content
+        };
+
+        integrity
+            .verify(&content)
+            .context(format!("Package archive was rejected: {}", archive.url))?;
+        Ok(content)
+    }
+
+    /// Returns the registry signing keys, or `None` if the registry publishes no keys.
+    ///
+    /// Keys are fetched once per registry. In offline mode, keys are read from npm's
+    /// cache and `None` is returned if they are not cached.
+    fn get_registry_keys(
+        &self,
+        registry_url: &url::Url,
+    ) -> Result<Option<Vec<signatures::RegistryKey>>> {
+        if let Some(keys) = self
+            .registry_keys_
+            .lock()
+            .expect("registry keys lock")
+            .get(registry_url)
+        {
+            return Ok(keys.clone());
+        }
+
+        let keys_url = registry_url.join("-/npm/v1/keys")?;
+        let keys = match get_registry_document(
+            self.npm_config()?,
+            self.http_client()?,
+            registry_url,
+            &keys_url,
+        )? {
+            Some(body) => Some(signatures::parse_keys(&body)?),
+            None => None,
+        };
+        self.registry_keys_
+            .lock()
+            .expect("registry keys lock")
+            .insert(registry_url.clone(), keys.clone());
+        Ok(keys)
+    }
+
+    /// Apply a registry query to each package name using concurrent workers.
+    ///
+    /// The number of workers is limited by npm's `maxsockets` option.
+    fn fetch_concurrently<'a, T, F>(
+        &self,
+        package_names: std::collections::BTreeSet<&'a str>,
+        fetch: F,
+    ) -> std::collections::HashMap<&'a str, Result<T>>
+    where
+        T: Send,
+        F: Fn(&str) -> Result<T> + Sync,
+    {
+        let max_sockets = self
+            .http_client()
+            .map(|http_client| http_client.get_max_sockets())
+            .unwrap_or(1);
+        let worker_count = std::cmp::min(max_sockets, package_names.len());
+        let unprocessed_package_names =
+            std::sync::Mutex::new(package_names.into_iter().collect::<Vec<_>>());
+        let results = std::sync::Mutex::new(std::collections::HashMap::new());
+
+        std::thread::scope(|scope| {
+            for _ in 0..worker_count {
+                scope.spawn(|| loop {
+                    let package_name = match unprocessed_package_names
+                        .lock()
+                        .expect("package names lock")
+                        .pop()
+                    {
+                        Some(v) => v,
+                        None => break,
+                    };
+                    let result = fetch(package_name);
+                    results
+                        .lock()
+                        .expect("results lock")
+                        .insert(package_name, result);
+                });
+            }
+        });
+        results.into_inner().expect("results lock")
+    }
+
+    /// Download a package version archive, verify it and extract it for review.
+    ///
+    /// The archive's top level `package/` directory is stripped, so that package files
+    /// are extracted directly into the destination directory, which must not exist.
+    pub fn extract_package_archive(
+        &self,
+        package_name: &str,
+        package_version: &Option<&str>,
+        destination: &std::path::Path,
+    ) -> Result<PackageArchive> {
+        let archive = self.get_package_archive(package_name, package_version)?;
+        let content = self.download_package_archive(&archive)?;
+        archive::extract(&content, destination).context(format!(
+            "Failed to extract package archive: {}",
+            archive.url
+        ))?;
+        Ok(archive)
+    }
+
+    /// Compare the published archive contents of two package versions.
+    ///
+    /// Version ranges and tags are resolved to the matching published versions. Both
+    /// archives are downloaded from the primary registry and verified. Returns file-level
+    /// and line-level differences, and flags added binary files, changed package.json
+    /// scripts and new dependencies.
+    pub fn diff_package_versions(
+        &self,
+        package_name: &str,
+        old_version: &str,
+        new_version: &str,
+    ) -> Result<PackageDiff> {
+        let npm_config = self.npm_config()?;
+        let registry_url = npm_config.get_registry_url(package_name)?;
+        let entry_json = get_registry_entry_json(
+            npm_config,
+            self.http_client()?,
+            &registry_url,
+            package_name,
+            PackumentFormat::Abbreviated,
+        )?;
+        let old_version = resolve_package_version(self, &entry_json, &Some(old_version))?;
+        let new_version = resolve_package_version(self, &entry_json, &Some(new_version))?;
+
+        let mut all_files = Vec::new();
+        for package_version in &[&old_version, &new_version] {
+            let archive = get_package_archive(&registry_url, &entry_json, package_version)?;
+            let content = self.download_package_archive(&archive)?;
+            let files = archive::read_files(&content)
+                .context(format!("Failed to read package archive: {}", archive.url))?;
+            all_files.push(files);
+        }
+        let (old_files, new_files) = (&all_files[0], &all_files[1]);
+
+        let file_diffs = diff::diff(old_files, new_files);
+        let flags = diff::get_flags(old_files, new_files, &file_diffs)?;
+        Ok(PackageDiff {
+            package_name: package_name.to_string(),
+            old_version,
+            new_version,
+            files: file_diffs,
+            flags,
+        })
+    }
+
+    /// Returns the package registry entries from each registry which can serve the
+    /// package and provides it. The primary registry entry is first.
+    ///
+    /// The primary registry is the registry npm would install the package from, given
+    /// scope registry mappings. Packages mapped to a scope registry are only requested
+    /// from that registry. Other packages are also requested from the default registry
+    /// mirrors. Secondary registry failures are recorded rather than returned.
+    fn get_registry_entries(&self, package_name: &str) -> Result<RegistryEntries> {
+        let npm_config = self.npm_config()?;
+        let registry_urls = npm_config.get_package_registry_urls(package_name)?;
+        let primary_registry_url = &registry_urls[0];
+        let entry_json = get_registry_entry_json(
+            npm_config,
+            self.http_client()?,
+            primary_registry_url,
+            package_name,
+            PackumentFormat::Abbreviated,
+        )?;
+        let mut registry_entries = RegistryEntries {
+            entries: vec![RegistryEntry {
+                registry_url: primary_registry_url.clone(),
+                entry_json,
+                is_primary: true,
+            }],
+            errors: Vec::new(),
+        };
+
+        for registry_url in &registry_urls[1..] {
+            match find_registry_entry_json(
+                npm_config,
+                self.http_client()?,
+                registry_url,
+                package_name,
+                PackumentFormat::Abbreviated,
+            ) {
+                Ok(Some(entry_json)) => registry_entries.entries.push(RegistryEntry {
+                    registry_url: registry_url.clone(),
+                    entry_json,
+                    is_primary: false,
+                }),
+                Ok(None) => {}
+                Err(error) => registry_entries.errors.push(RegistryError {
+                    registry_url: registry_url.clone(),
+                    error: format!("{:#}", error),
+                }),
+            }
+        }
+        Ok(registry_entries)
     }
 }
 
@@ -35,68 +543,46 @@ impl vouch_lib::extension::Extension for JsExtension {
 
     /// Returns a list of dependencies for the given package.
     ///
-    /// Returns one package dependencies structure per registry.
+    /// Returns one package dependencies structure per registry. Dependencies are resolved
+    /// natively from registry metadata unless the `--npm-resolver` argument is given,
+    /// in which case npm is used.
     fn identify_package_dependencies(
         &self,
         package_name: &str,
         package_version: &Option<&str>,
-        _extension_args: &Vec<String>,
+        extension_args: &Vec<String>,
     ) -> Result<Vec<vouch_lib::extension::PackageDependencies>> {
-        // npm install is-even@1.0.0 --package-lock-only
-        let tmp_dir = tempdir::TempDir::new("vouch_js_identify_package_dependencies")?;
-        let tmp_directory_path = tmp_dir.path().to_path_buf();
-
-        let package = if let Some(package_version) = package_version {
-            format!(
-                "{name}@{version}",
-                name = package_name,
-                version = package_version
-            )
-        } else {
-            package_name.to_string()
-        };
-        let args = vec!["install", package.as_str(), "--package-lock-only"];
+        let npm_config = self.npm_config()?;
+        let use_npm_resolver = extension_args.iter().any(|v| v == "--npm-resolver");
 
-        std::process::Command::new("npm")
-            .args(args)
-            .stdin(std::process::Stdio::null())
-            .stderr(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .current_dir(&tmp_directory_path)
-            .output()?;
-
-        let package_lock_path = tmp_directory_path.join("package-lock.json");
-        let dependencies = npm::get_dependencies(&package_lock_path, false)?;
-
-        let package_version = if let Some(package_version) = package_version {
-            vouch_lib::extension::VersionParseResult::Ok(package_version.to_string())
+        let (package_version, dependencies) = if use_npm_resolver {
+            npm::identify_package_dependencies(
+                package_name,
+                package_version,
+                npm_config.is_offline(),
+            )?
         } else {
-            // Extract target package version from dependencies so as to remove from the dependencies vector.
-            let mut target_package_instances: Vec<_> = dependencies
-                .iter()
-                .filter(|d| d.name == package_name)
-                .cloned()
-                .collect();
-            target_package_instances.sort();
-            target_package_instances.reverse();
-            let target_package_instance = target_package_instances.first().ok_or(format_err!(
-                "Failed to find target package in dependencies list."
-            ))?;
-            target_package_instance.version.clone()
+            let (package_version, dependencies) = resolver::resolve_dependencies(
+                npm_config,
+                self.http_client()?,
+                package_name,
+                package_version,
+            )?;
+            (Ok(package_version), dependencies)
         };
 
-        let dependencies = dependencies
-            .into_iter()
-            .filter(|d| d.name != package_name && d.version != package_version)
-            .collect();
-
+        let registry_url = npm_config.get_registry_url(package_name)?;
         Ok(vec![vouch_lib::extension::PackageDependencies {
             package_version: package_version,
-            registry_host_name: npm::get_registry_host_name(),
+            registry_host_name: npmrc::get_registry_host_name(&registry_url)?,
             dependencies: dependencies,
         }])
     }
 
+    /// Returns registry dependencies defined in the nearest dependency definition files.
+    ///
+    /// Packages which are not fetched from a package registry are excluded. They are
+    /// reported by `JsExtension::identify_excluded_dependencies`.
     fn identify_file_defined_dependencies(
         &self,
         working_directory: &std::path::PathBuf,
@@ -104,85 +590,226 @@ impl vouch_lib::extension::Extension for JsExtension {
     ) -> Result<Vec<vouch_lib::extension::FileDefinedDependencies>> {
         let include_dev_dependencies = extension_args.iter().any(|v| v == "--dev");
 
-        // Identify all dependency definition files.
-        let dependency_files = match identify_dependency_files(&working_directory) {
-            Some(v) => v,
-            None => return Ok(Vec::new()),
-        };
+        // Reject lockfiles which do not agree with the registry.
+        if extension_args.iter().any(|v| v == "--audit") {
+            let issues =
+                self.audit_dependency_files(working_directory, include_dev_dependencies)?;
+            if !issues.is_empty() {
+                let issues: Vec<_> = issues.iter().map(|issue| issue.to_string()).collect();
+                return Err(format_err!(
+                    "Lockfile audit found {} issue(s):\n{}",
+                    issues.len(),
+                    issues.join("\n")
+                ));
+            }
+        }
+
+        let npm_config = self.npm_config()?;
+        let default_registry_host_name =
+            npmrc::get_registry_host_name(&npm_config.get_default_registry_url()?)?;
 
-        // Read all dependencies definitions files.
         let mut all_dependency_specs = Vec::new();
-        for dependency_file in dependency_files {
-            // TODO: Add support for parsing all definition file types.
-            let (dependencies, registry_host_name) = match dependency_file.r#type {
-                DependencyFileType::Npm => (
-                    npm::get_dependencies(&dependency_file.path, include_dev_dependencies)?,
-                    npm::get_registry_host_name(),
-                ),
-            };
-            all_dependency_specs.push(vouch_lib::extension::FileDefinedDependencies {
-                path: dependency_file.path,
-                registry_host_name: registry_host_name,
-                dependencies: dependencies,
-            });
-        }
+        for (path, dependencies) in
+            read_dependency_files(working_directory, include_dev_dependencies)?
+        {
+            // Dependencies grouped by the host name of the registry npm would fetch
+            // them from, given scope registry mappings.
+            let mut registries_dependencies = std::collections::BTreeMap::new();
+            registries_dependencies.insert(default_registry_host_name.clone(), Vec::new());
+            for dependency in dependencies.registry_dependencies {
+                let registry_url = npm_config.get_registry_url(&dependency.name)?;
+                registries_dependencies
+                    .entry(npmrc::get_registry_host_name(&registry_url)?)
+                    .or_insert_with(Vec::new)
+                    .push(dependency);
+            }
 
+            for (registry_host_name, dependencies) in registries_dependencies {
+                all_dependency_specs.push(vouch_lib::extension::FileDefinedDependencies {
+                    path: path.clone(),
+                    registry_host_name,
+                    dependencies,
+                });
+            }
+        }
         Ok(all_dependency_specs)
     }
 
+    /// Returns package metadata from each configured registry which provides the package.
+    ///
+    /// The primary registry is the registry npm would install the package from, given
+    /// scope registry mappings. The package version is resolved against the primary
+    /// registry. Other configured registries are included if they provide the same
+    /// package version, so that their artifacts can be compared.
+    ///
+    /// Package versions with invalid registry signatures are errors. Use
+    /// `registries_packages_metadata` to also obtain the verification status and secondary
+    /// registry errors.
     fn registries_package_metadata(
         &self,
         package_name: &str,
         package_version: &Option<&str>,
     ) -> Result<Vec<vouch_lib::extension::RegistryPackageMetadata>> {
-        let package_version = match package_version {
-            Some(v) => Some(v.to_string()),
-            None => get_latest_version(&package_name)?,
-        }
-        .ok_or(format_err!("Failed to find package version."))?;
+        let registry_entries = self.get_registry_entries(package_name)?;
+        let package_metadata = get_registries_package_metadata(
+            self,
+            package_name,
+            package_version,
+            &registry_entries,
+        )?;
+        Ok(package_metadata.registries)
+    }
+}
 
-        // Query remote package registry for given package.
-        let human_url = get_registry_human_url(&self, &package_name, &package_version)?;
+/// Package registry entry (packument) from a single registry.
+#[derive(Debug, Clone)]
+struct RegistryEntry {
+    registry_url: url::Url,
+    entry_json: serde_json::Value,
+    is_primary: bool,
+}
 
-        // Currently, only one registry is supported. Therefore simply extract.
-        let registry_host_name = self
-            .registries()
-            .first()
-            .ok_or(format_err!(
-                "Code error: vector of registry host names is empty."
-            ))?
-            .clone();
+/// Package registry entries from the registries which can serve a package.
+#[derive(Debug, Clone)]
+struct RegistryEntries {
+    /// Entries of the registries which provide the package. The primary registry is first.
+    entries: Vec<RegistryEntry>,
 
-        let entry_json = get_registry_entry_json(&package_name)?;
-        let artifact_url = get_archive_url(&entry_json, &package_version)?;
+    /// Secondary registries which failed to respond.
+    errors: Vec<RegistryError>,
+}
 
-        Ok(vec![vouch_lib::extension::RegistryPackageMetadata {
-            registry_host_name: registry_host_name,
-            human_url: human_url.to_string(),
-            artifact_url: artifact_url.to_string(),
-            is_primary: true,
-            package_version: package_version,
-        }])
+/// Returns package version metadata for each registry entry which provides the version.
+///
+/// The package version is resolved using the primary registry entry, which is first, and
+/// verified against the primary registry.
+fn get_registries_package_metadata(
+    extension: &JsExtension,
+    package_name: &str,
+    package_version: &Option<&str>,
+    registry_entries: &RegistryEntries,
+) -> Result<PackageMetadata> {
+    let primary_entry_json = &registry_entries
+        .entries
+        .first()
+        .ok_or(format_err!("Failed to find primary registry entry."))?
+        .entry_json;
+    let package_version = resolve_package_version(extension, primary_entry_json, package_version)?;
+
+    // Reject package versions with invalid registry signatures.
+    let primary_entry = &registry_entries.entries[0];
+    let verification = verify_package_version(
+        extension,
+        &primary_entry.registry_url,
+        &primary_entry.entry_json,
+        package_name,
+        &package_version,
+    )?;
+
+    let mut all_registries_package_metadata = Vec::new();
+    for registry_entry in &registry_entries.entries {
+        if !registry_entry.is_primary
+            && registry_entry.entry_json["versions"][&package_version].is_null()
+        {
+            continue;
+        }
+        all_registries_package_metadata.push(get_registry_package_metadata(
+            extension,
+            &registry_entry.registry_url,
+            &registry_entry.entry_json,
+            package_name,
+            &package_version,
+            registry_entry.is_primary,
+        )?);
+    }
+    Ok(PackageMetadata {
+        registries: all_registries_package_metadata,
+        registry_errors: registry_entries.errors.clone(),
+        verification,
+    })
+}
+
+/// Resolve version ranges and dist-tags to a concrete version.
+///
+/// If no version is given, the version tagged by npm's `tag` option (default `latest`)
+/// is returned.
+fn resolve_package_version(
+    extension: &JsExtension,
+    registry_entry_json: &serde_json::Value,
+    package_version: &Option<&str>,
+) -> Result<String> {
+    let dist_tag = extension.npm_config()?.get("tag").unwrap_or("latest");
+    match package_version {
+        Some(v) => Some(resolver::pick_version(registry_entry_json, v)?),
+        None => get_latest_version(registry_entry_json, dist_tag)?,
     }
+    .ok_or(format_err!("Failed to find package version."))
+}
+
+/// Returns package version metadata for a single registry.
+fn get_registry_package_metadata(
+    extension: &JsExtension,
+    registry_url: &url::Url,
+    registry_entry_json: &serde_json::Value,
+    package_name: &str,
+    package_version: &str,
+    is_primary: bool,
+) -> Result<vouch_lib::extension::RegistryPackageMetadata> {
+    let human_url = get_registry_human_url(extension, registry_url, package_name, package_version)?;
+    let registry_host_name = npmrc::get_registry_host_name(registry_url)?;
+    let archive = get_package_archive(registry_url, registry_entry_json, package_version)?;
+
+    Ok(vouch_lib::extension::RegistryPackageMetadata {
+        registry_host_name,
+        human_url: human_url.to_string(),
+        artifact_url: archive.url.to_string(),
+        is_primary,
+        package_version: package_version.to_string(),
+    })
 }
 
-/// Given package name, return latest version.
-fn get_latest_version(package_name: &str) -> Result<Option<String>> {
-    let json = get_registry_entry_json(&package_name)?;
-    let versions = json["versions"]
-        .as_object()
-        .ok_or(format_err!("Failed to find versions JSON section."))?;
-    let latest_version = versions.keys().last();
-    Ok(latest_version.cloned())
+/// Given package registry entry and dist-tag, return the tagged version.
+///
+/// If the package has no `latest` dist-tag, the highest stable version which is
+/// not deprecated is returned instead.
+fn get_latest_version(
+    registry_entry_json: &serde_json::Value,
+    dist_tag: &str,
+) -> Result<Option<String>> {
+    if let Some(version) = registry_entry_json["dist-tags"][dist_tag].as_str() {
+        return Ok(Some(version.to_string()));
+    }
+    if dist_tag != "latest" {
+        return Err(format_err!(
+            "Failed to find dist-tag {} for package {}.",
+            dist_tag,
+            registry_entry_json["name"].as_str().unwrap_or_default()
+        ));
+    }
+    Ok(resolver::get_highest_stable_version(registry_entry_json))
 }
 
 fn get_registry_human_url(
     extension: &JsExtension,
+    registry_url: &url::Url,
     package_name: &str,
     package_version: &str,
 ) -> Result<url::Url> {
     // Example return value: https://www.npmjs.com/package/d3/v/6.5.0
-    let handlebars_registry = handlebars::Handlebars::new();
+    // Scoped packages: https://www.npmjs.com/package/@babel/core/v/7.12.10
+    package_name::validate(package_name)?;
+
+    // Other registries have no standard web interface. Link to the version manifest instead.
+    if !npmrc::is_default_registry(registry_url) {
+        return Ok(registry_url.join(&format!(
+            "{}/{}",
+            package_name::escape(package_name),
+            package_version
+        ))?);
+    }
+
+    let mut handlebars_registry = handlebars::Handlebars::new();
+    handlebars_registry.register_escape_fn(handlebars::no_escape);
     let url = handlebars_registry.render_template(
         &extension.registry_human_url_template_,
         &maplit::btreemap! {
@@ -193,35 +820,456 @@ fn get_registry_human_url(
     Ok(url::Url::parse(url.as_str())?)
 }
 
-fn get_registry_entry_json(package_name: &str) -> Result<serde_json::Value> {
-    let handlebars_registry = handlebars::Handlebars::new();
+/// Send a GET request to a registry, including any configured credentials.
+///
+/// Returns `None` if the registry responds that the resource was not found.
+/// Responses to conditional requests may have status `304 Not Modified`, and
+/// responses to content negotiated requests may have status `406 Not Acceptable`.
+fn send_registry_request(
+    npm_config: &npmrc::Config,
+    http_client: &http::Client,
+    registry_url: &url::Url,
+    url: &url::Url,
+    mut headers: reqwest::header::HeaderMap,
+) -> Result<Option<reqwest::blocking::Response>> {
+    if let Some(authorization) = npm_config.get_authorization(url, registry_url)? {
+        let mut header_value = reqwest::header::HeaderValue::from_str(&authorization)
+            .map_err(|_| format_err!("Invalid registry credentials for: {}", registry_url))?;
+        header_value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, header_value);
+    }
+
+    let response = http_client.get(url, headers)?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(format_err!(
+            "Registry request was not authorized ({}): {}\n\
+            Check the credentials configured for this registry in .npmrc.",
+            status,
+            url
+        ));
+    }
+    if !status.is_success()
+        && status != reqwest::StatusCode::NOT_MODIFIED
+        && status != reqwest::StatusCode::NOT_ACCEPTABLE
+    {
+        return Err(format_err!("Registry request failed ({}): {}", status, url));
+    }
+    Ok(Some(response))
+}
+
+/// Registry entry (packument) document formats.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PackumentFormat {
+    /// Abbreviated document containing the fields needed to install packages.
+    ///
+    /// Includes dist-tags, version dependencies, `deprecated` and `dist` fields.
+    Abbreviated,
+
+    /// Complete document, including fields such as `time` and `maintainers`.
+    Full,
+}
+
+impl PackumentFormat {
+    /// Returns the HTTP Accept header value used to request the document format.
+    pub fn get_accept_header(&self) -> &'static str {
+        match self {
+            Self::Abbreviated => {
+                "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*"
+            }
+            Self::Full => "application/json",
+        }
+    }
+
+    /// Returns a name which identifies the document format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Abbreviated => "abbreviated",
+            Self::Full => "full",
+        }
+    }
+}
+
+fn get_registry_entry_json(
+    npm_config: &npmrc::Config,
+    http_client: &http::Client,
+    registry_url: &url::Url,
+    package_name: &str,
+    format: PackumentFormat,
+) -> Result<serde_json::Value> {
+    let entry_json =
+        find_registry_entry_json(npm_config, http_client, registry_url, package_name, format)?;
+    if entry_json.is_none() && npm_config.is_offline() {
+        return Err(format_err!(
+            "Package {} is not available offline: it was not found in the local cache \
+            or npm's cache for registry {}\n\
+            Disable npm's offline option to fetch the package from the registry.",
+            package_name,
+            registry_url
+        ));
+    }
+    entry_json.ok_or(format_err!(
+        "Failed to find package {} in registry: {}",
+        package_name,
+        registry_url
+    ))
+}
+
+/// Returns a registry document, or `None` if the registry does not provide it.
+///
+/// In offline mode, the document is read from npm's cache and `None` is returned if
+/// it is not cached.
+fn get_registry_document(
+    npm_config: &npmrc::Config,
+    http_client: &http::Client,
+    registry_url: &url::Url,
+    url: &url::Url,
+) -> Result<Option<Vec<u8>>> {
+    if npm_config.is_offline() {
+        return Ok(cacache::get(npm_config, url)?.map(|(body, _)| body));
+    }
+    let mut response = match send_registry_request(
+        npm_config,
+        http_client,
+        registry_url,
+        url,
+        reqwest::header::HeaderMap::new(),
+    )? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let mut body = Vec::new();
+    response.read_to_end(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Returns the registry entry (packument) for a package, or `None` if the registry
+/// does not provide the package.
+///
+/// Packuments are cached on disk. Stale cache entries are revalidated using
+/// conditional requests. In offline mode, packuments are read from the local cache
+/// or npm's cache and `None` is returned if neither has the package.
+///
+/// The full document is requested if the registry does not serve the abbreviated
+/// document format.
+fn find_registry_entry_json(
+    npm_config: &npmrc::Config,
+    http_client: &http::Client,
+    registry_url: &url::Url,
+    package_name: &str,
+    format: PackumentFormat,
+) -> Result<Option<serde_json::Value>> {
+    package_name::validate(package_name)?;
+    let mut handlebars_registry = handlebars::Handlebars::new();
+    handlebars_registry.register_escape_fn(handlebars::no_escape);
     let json_url = handlebars_registry.render_template(
-        "https://registry.npmjs.com/{{package_name}}",
-        &maplit::btreemap! {"package_name" => package_name},
+        "{{registry_url}}{{package_name}}",
+        &maplit::btreemap! {
+            "registry_url" => registry_url.to_string(),
+            "package_name" => package_name::escape(package_name),
+        },
     )?;
 
-    let mut result = reqwest::blocking::get(&json_url.to_string())?;
+    let json_url = url::Url::parse(&json_url)?;
+
+    let cache = cache::Cache::new(npm_config)?;
+    let cached_entry = cache
+        .get(registry_url, package_name, format)
+        .filter(|entry| entry.url == json_url.as_str());
+    if let Some(entry) = &cached_entry {
+        if cache.is_fresh(entry) {
+            return Ok(Some(entry.body.clone()));
+        }
+    }
+    if npm_config.is_offline() {
+        return get_npm_cached_entry_json(npm_config, &json_url, format);
+    }
+
+    let mut headers = cached_entry
+        .as_ref()
+        .map(|entry| entry.get_revalidation_headers())
+        .unwrap_or_default();
+    headers.insert(
+        reqwest::header::ACCEPT,
+        reqwest::header::HeaderValue::from_static(format.get_accept_header()),
+    );
+    let mut result =
+        match send_registry_request(npm_config, http_client, registry_url, &json_url, headers)? {
+            Some(v) => v,
+            None => {
+                cache.remove(registry_url, package_name, format);
+                return Ok(None);
+            }
+        };
+
+    if result.status() == reqwest::StatusCode::NOT_ACCEPTABLE {
+        if format == PackumentFormat::Abbreviated {
+            return find_registry_entry_json(
+                npm_config,
+                http_client,
+                registry_url,
+                package_name,
+                PackumentFormat::Full,
+            );
+        }
+        return Err(format_err!(
+            "Registry did not accept request for JSON document: {}",
+            json_url
+        ));
+    }
+
+    if result.status() == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(mut entry) = cached_entry {
+            entry.refresh();
+            // Caching is best effort: failing to update the cache does not fail the request.
+            cache
+                .insert(registry_url, package_name, format, &entry)
+                .ok();
+            return Ok(Some(entry.body));
+        }
+        return Err(format_err!(
+            "Registry responded not modified to unconditional request: {}",
+            json_url
+        ));
+    }
+
     let mut body = String::new();
     result.read_to_string(&mut body)?;
+    let body: serde_json::Value =
+        serde_json::from_str(&body).context(format!("JSON was not well-formatted:\n{}", body))?;
 
-    Ok(serde_json::from_str(&body).context(format!("JSON was not well-formatted:\n{}", body))?)
+    let entry = cache::Entry::new(&json_url, result.headers(), body);
+    cache
+        .insert(registry_url, package_name, format, &entry)
+        .ok();
+    Ok(Some(entry.body))
 }
 
-fn get_archive_url(
-    registry_entry_json: &serde_json::Value,
+/// Returns a registry entry (packument) from npm's cache.
+///
+/// npm's default registry URL may differ from the configured URL, so requests to the
+/// default registry are looked up under each default registry host name. npm usually
+/// caches abbreviated documents, which can not be used if the full document is required.
+fn get_npm_cached_entry_json(
+    npm_config: &npmrc::Config,
+    json_url: &url::Url,
+    format: PackumentFormat,
+) -> Result<Option<serde_json::Value>> {
+    let abbreviated_media_type = "application/vnd.npm.install-v1+json";
+    let mut json_urls = vec![json_url.clone()];
+    if npmrc::is_default_registry(json_url) {
+        for host_name in npmrc::DEFAULT_REGISTRY_HOST_NAMES {
+            let mut url = json_url.clone();
+            url.set_host(Some(host_name))?;
+            if !json_urls.contains(&url) {
+                json_urls.push(url);
+            }
+        }
+    }
+
+    for url in json_urls {
+        if let Some((body, metadata)) = cacache::get(npm_config, &url)? {
+            let accept = metadata["reqHeaders"]["accept"]
+                .as_str()
+                .unwrap_or_default();
+            if format == PackumentFormat::Full && accept.starts_with(abbreviated_media_type) {
+                continue;
+            }
+            return Ok(Some(
+                serde_json::from_slice(&body)
+                    .context(format!("Failed to parse npm cache entry: {}", url))?,
+            ));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the conventional registry tarball URL for a package version.
+///
+/// Example: `@babel/core` version `7.12.10` yields
+/// https://registry.npmjs.com/@babel/core/-/core-7.12.10.tgz
+fn get_default_archive_url(
+    registry_url: &url::Url,
+    package_name: &str,
     package_version: &str,
 ) -> Result<url::Url> {
-    Ok(url::Url::parse(
-        registry_entry_json["versions"][package_version]["dist"]["tarball"]
+    package_name::validate(package_name)?;
+    let mut handlebars_registry = handlebars::Handlebars::new();
+    handlebars_registry.register_escape_fn(handlebars::no_escape);
+    let url = handlebars_registry.render_template(
+        "{{registry_url}}{{package_name}}/-/{{unscoped_package_name}}-{{package_version}}.tgz",
+        &maplit::btreemap! {
+            "registry_url" => registry_url.as_str(),
+            "package_name" => package_name,
+            "unscoped_package_name" => package_name::unscoped(package_name),
+            "package_version" => package_version,
+        },
+    )?;
+    Ok(url::Url::parse(url.as_str())?)
+}
+
+/// Package archive (tarball) location and expected content hashes.
+#[derive(Debug, Clone)]
+pub struct PackageArchive {
+    pub registry_url: url::Url,
+    pub url: url::Url,
+
+    /// Subresource Integrity hashes from registry `dist.integrity` metadata.
+    ///
+    /// Example: `sha512-<base64 digest>`.
+    pub integrity: Option<String>,
+
+    /// Hex encoded sha1 digest from registry `dist.shasum` metadata.
+    pub shasum: Option<String>,
+}
+
+impl PackageArchive {
+    fn get_integrity(&self) -> Result<integrity::Integrity> {
+        integrity::Integrity::from_values(self.integrity.as_deref(), self.shasum.as_deref())
+    }
+}
+
+fn get_package_archive(
+    registry_url: &url::Url,
+    registry_entry_json: &serde_json::Value,
+    package_version: &str,
+) -> Result<PackageArchive> {
+    let version_json = &registry_entry_json["versions"][package_version];
+    if version_json.is_null() {
+        return Err(format_err!(
+            "Failed to find package version in registry: {}",
+            package_version
+        ));
+    }
+    let url = match version_json["dist"]["tarball"].as_str() {
+        Some(url) => url::Url::parse(url)?,
+        None => {
+            let package_name = registry_entry_json["name"]
+                .as_str()
+                .ok_or(format_err!("Failed to parse package archive URL."))?;
+            get_default_archive_url(registry_url, package_name, package_version)?
+        }
+    };
+    Ok(PackageArchive {
+        registry_url: registry_url.clone(),
+        url,
+        integrity: version_json["dist"]["integrity"]
+            .as_str()
+            .map(|v| v.to_string()),
+        shasum: version_json["dist"]["shasum"]
             .as_str()
-            .ok_or(format_err!("Failed to parse package archive URL."))?,
-    )?)
+            .map(|v| v.to_string()),
+    })
+}
+
+/// Package version metadata from each registry which provides the version.
+#[derive(Debug, Clone)]
+pub struct PackageMetadata {
+    /// Metadata per registry. The primary registry is first.
+    pub registries: Vec<vouch_lib::extension::RegistryPackageMetadata>,
+
+    /// Secondary registries which failed to respond, so that their metadata is missing.
+    pub registry_errors: Vec<RegistryError>,
+
+    /// Verification result for the package version from the primary registry.
+    pub verification: PackageVerification,
+}
+
+/// Failure to query a secondary registry for a package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryError {
+    pub registry_url: url::Url,
+    pub error: String,
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.registry_url, self.error)
+    }
+}
+
+/// Registry signature verification result for a package version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageVerification {
+    pub signatures: SignatureStatus,
+}
+
+impl std::fmt::Display for PackageVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Registry signature: {}", self.signatures)
+    }
+}
+
+/// Verify the registry signatures of a package version.
+///
+/// The full registry entry is fetched if a signature uses an expiring key and the
+/// abbreviated entry does not record the publish time, which is compared with the key
+/// expiry time.
+fn verify_package_version(
+    extension: &JsExtension,
+    registry_url: &url::Url,
+    registry_entry_json: &serde_json::Value,
+    package_name: &str,
+    package_version: &str,
+) -> Result<PackageVerification> {
+    let npm_config = extension.npm_config()?;
+    let is_offline = npm_config.is_offline();
+    let version_json = &registry_entry_json["versions"][package_version];
+    if version_json.is_null() {
+        return Err(format_err!(
+            "Failed to find package version in registry: {}",
+            package_version
+        ));
+    }
+
+    let signature_status = match extension.get_registry_keys(registry_url)? {
+        Some(keys) => {
+            let mut publish_time = registry_entry_json["time"][package_version]
+                .as_str()
+                .map(|v| v.to_string());
+            if publish_time.is_none() && signatures::requires_publish_time(version_json, &keys) {
+                publish_time = find_registry_entry_json(
+                    npm_config,
+                    extension.http_client()?,
+                    registry_url,
+                    package_name,
+                    PackumentFormat::Full,
+                )?
+                .and_then(|entry_json| {
+                    entry_json["time"][package_version]
+                        .as_str()
+                        .map(|v| v.to_string())
+                });
+            }
+            signatures::verify(
+                package_name,
+                package_version,
+                version_json,
+                &keys,
+                publish_time.as_deref(),
+            )?
+        }
+        None if is_offline => SignatureStatus::Unavailable,
+        None => SignatureStatus::Unsupported,
+    };
+
+    Ok(PackageVerification {
+        signatures: signature_status,
+    })
 }
 
 /// Package dependency file types.
-#[derive(Debug, Copy, Clone, strum_macros::EnumIter)]
+#[derive(Debug, Copy, Clone, PartialEq, strum_macros::EnumIter)]
 enum DependencyFileType {
     Npm,
+    NpmShrinkwrap,
+    Yarn,
+    Pnpm,
+    Bun,
+    PackageJson,
 }
 
 impl DependencyFileType {
@@ -229,6 +1277,11 @@ impl DependencyFileType {
     pub fn file_name(&self) -> std::path::PathBuf {
         match self {
             Self::Npm => std::path::PathBuf::from("package-lock.json"),
+            Self::NpmShrinkwrap => std::path::PathBuf::from("npm-shrinkwrap.json"),
+            Self::Yarn => std::path::PathBuf::from("yarn.lock"),
+            Self::Pnpm => std::path::PathBuf::from("pnpm-lock.yaml"),
+            Self::Bun => std::path::PathBuf::from("bun.lock"),
+            Self::PackageJson => std::path::PathBuf::from("package.json"),
         }
     }
 }
@@ -240,14 +1293,73 @@ struct DependencyFile {
     path: std::path::PathBuf,
 }
 
+/// Read dependencies from each identified dependency definition file.
+fn read_dependency_files(
+    working_directory: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<Vec<(std::path::PathBuf, npm::FileDependencies)>> {
+    let dependency_files = match identify_dependency_files(working_directory) {
+        Some(v) => v,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut all_dependencies = Vec::new();
+    for dependency_file in dependency_files {
+        let path = &dependency_file.path;
+        let dependencies = match dependency_file.r#type {
+            DependencyFileType::Npm | DependencyFileType::NpmShrinkwrap => {
+                npm::get_dependencies(path, include_dev_dependencies)?
+            }
+            DependencyFileType::Yarn => yarn::get_dependencies(path, include_dev_dependencies)?,
+            DependencyFileType::Pnpm => pnpm::get_dependencies(path, include_dev_dependencies)?,
+            DependencyFileType::Bun => bun::get_dependencies(path, include_dev_dependencies)?,
+            DependencyFileType::PackageJson => {
+                package_json::get_dependencies(path, include_dev_dependencies)?
+            }
+        };
+        all_dependencies.push((dependency_file.path, dependencies));
+    }
+    Ok(all_dependencies)
+}
+
+/// Dependencies which are not fetched from a package registry.
+#[derive(Debug, Clone)]
+pub struct ExcludedDependencies {
+    /// Dependency definition file path.
+    pub path: std::path::PathBuf,
+
+    /// Resolution protocol. For example: `workspace`, `patch`, `git` or `file`.
+    pub protocol: String,
+    pub dependencies: Vec<vouch_lib::extension::Dependency>,
+}
+
+impl std::fmt::Display for ExcludedDependencies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self
+            .dependencies
+            .iter()
+            .map(|dependency| dependency.name.as_str())
+            .collect();
+        write!(
+            f,
+            "{}: {} dependencies are not fetched from a package registry: {}",
+            self.path.display(),
+            self.protocol,
+            names.join(", ")
+        )
+    }
+}
+
 /// Returns a vector of identified package dependency definition files.
 ///
 /// Walks up the directory tree directory tree until the first positive result is found.
+/// Lockfiles take precedence. If no lockfile is found, the nearest package.json file is used.
 fn identify_dependency_files(
     working_directory: &std::path::PathBuf,
 ) -> Option<Vec<DependencyFile>> {
     assert!(working_directory.is_absolute());
     let mut working_directory = working_directory.clone();
+    let mut package_json_file: Option<DependencyFile> = None;
 
     loop {
         // If at least one target is found, assume package is present.
@@ -255,6 +1367,17 @@ fn identify_dependency_files(
 
         let mut dependency_files: Vec<DependencyFile> = Vec::new();
         for dependency_file_type in DependencyFileType::iter() {
+            if dependency_file_type == DependencyFileType::PackageJson {
+                let target_absolute_path = working_directory.join(dependency_file_type.file_name());
+                if package_json_file.is_none() && target_absolute_path.is_file() {
+                    package_json_file = Some(DependencyFile {
+                        r#type: dependency_file_type,
+                        path: target_absolute_path,
+                    });
+                }
+                continue;
+            }
+
             let target_absolute_path = working_directory.join(dependency_file_type.file_name());
             if target_absolute_path.is_file() {
                 found_dependency_file = true;
@@ -265,6 +1388,13 @@ fn identify_dependency_files(
             }
         }
         if found_dependency_file {
+            // npm ignores package-lock.json when npm-shrinkwrap.json is present.
+            if dependency_files
+                .iter()
+                .any(|f| f.r#type == DependencyFileType::NpmShrinkwrap)
+            {
+                dependency_files.retain(|f| f.r#type != DependencyFileType::Npm);
+            }
             return Some(dependency_files);
         }
 
@@ -276,5 +1406,589 @@ fn identify_dependency_files(
         // Move further up the directory tree.
         working_directory.pop();
     }
-    None
+    package_json_file.map(|f| vec![f])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vouch_lib::extension::Extension;
+
+    fn get_core_packument() -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "name": "@babel/core",
+            "dist-tags": {"latest": "7.12.10"},
+            "versions": {
+                "7.12.3": {
+                    "name": "@babel/core",
+                    "version": "7.12.3",
+                    "dependencies": {"@babel/types": "^7.12.1"}
+                },
+                "7.12.10": {
+                    "name": "@babel/core",
+                    "version": "7.12.10"
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn get_types_packument() -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "name": "@babel/types",
+            "dist-tags": {"latest": "7.12.10"},
+            "versions": {
+                "7.12.1": {"name": "@babel/types", "version": "7.12.1"},
+                "7.12.10": {"name": "@babel/types", "version": "7.12.10"}
+            }
+        }))
+        .unwrap()
+    }
+
+    /// Returns a gzip compressed package archive given (path, content) files.
+    fn get_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("package/{}", path), content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Returns an extension with a default registry and a `@babel` scope registry.
+    fn get_extension() -> (JsExtension, testing::Server, testing::Server) {
+        let default_registry = testing::Server::new(
+            "127.0.0.1",
+            vec![("/@babel%2fcore", 200, get_core_packument())],
+        );
+        let scope_registry = testing::Server::new(
+            "localhost",
+            vec![
+                ("/@babel%2fcore", 200, get_core_packument()),
+                ("/@babel%2ftypes", 200, get_types_packument()),
+            ],
+        );
+        let npm_config = testing::get_npm_config(&format!(
+            "registry={}\n@babel:registry={}\n",
+            default_registry.url(),
+            scope_registry.url()
+        ));
+        (
+            JsExtension::from_npm_config(Ok(npm_config)),
+            default_registry,
+            scope_registry,
+        )
+    }
+
+    #[test]
+    fn test_get_default_archive_url() -> Result<()> {
+        let cases = vec![
+            (
+                "https://registry.npmjs.com/",
+                "@babel/core",
+                "https://registry.npmjs.com/@babel/core/-/core-7.12.10.tgz",
+            ),
+            (
+                "https://npm.example.com/api/npm/",
+                "@babel/core",
+                "https://npm.example.com/api/npm/@babel/core/-/core-7.12.10.tgz",
+            ),
+            (
+                "https://registry.npmjs.com/",
+                "d3",
+                "https://registry.npmjs.com/d3/-/d3-7.12.10.tgz",
+            ),
+        ];
+        for (registry_url, package_name, expected_url) in cases {
+            let url =
+                get_default_archive_url(&url::Url::parse(registry_url)?, package_name, "7.12.10")?;
+            assert_eq!(url.as_str(), expected_url);
+        }
+        assert!(get_default_archive_url(
+            &url::Url::parse("https://registry.npmjs.com/")?,
+            "@babel/../core",
+            "7.12.10"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_registry_human_url() -> Result<()> {
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config("")));
+        let cases = vec![
+            (
+                "https://registry.npmjs.com/",
+                "https://www.npmjs.com/package/@babel/core/v/7.12.10",
+            ),
+            (
+                "https://registry.npmjs.org/",
+                "https://www.npmjs.com/package/@babel/core/v/7.12.10",
+            ),
+            (
+                "https://npm.example.com/api/npm/",
+                "https://npm.example.com/api/npm/@babel%2fcore/7.12.10",
+            ),
+        ];
+        for (registry_url, expected_url) in cases {
+            let url = get_registry_human_url(
+                &extension,
+                &url::Url::parse(registry_url)?,
+                "@babel/core",
+                "7.12.10",
+            )?;
+            assert_eq!(url.as_str(), expected_url);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_registry_entry_json() -> Result<()> {
+        let (extension, _, scope_registry) = get_extension();
+        let entry_json = find_registry_entry_json(
+            extension.npm_config()?,
+            extension.http_client()?,
+            scope_registry.url(),
+            "@babel/core",
+            PackumentFormat::Abbreviated,
+        )?;
+        assert_eq!(entry_json.unwrap()["name"], "@babel/core");
+
+        let entry_json = find_registry_entry_json(
+            extension.npm_config()?,
+            extension.http_client()?,
+            scope_registry.url(),
+            "@babel/missing",
+            PackumentFormat::Abbreviated,
+        )?;
+        assert!(entry_json.is_none());
+        assert_eq!(
+            scope_registry.requests(),
+            vec!["/@babel%2fcore", "/@babel%2fmissing"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_registry_entry_json_revalidation() -> Result<()> {
+        let core_packument = get_core_packument();
+        let registry = testing::Server::with_headers(
+            "127.0.0.1",
+            vec![
+                (
+                    "/@babel%2fcore",
+                    200,
+                    vec![
+                        ("ETag", "\"v1\""),
+                        ("Last-Modified", "Tue, 15 Dec 2020 10:00:00 GMT"),
+                    ],
+                    core_packument,
+                ),
+                ("/@babel%2fcore", 304, vec![], vec![]),
+            ],
+        );
+        let find_entry_json = |npm_config: &npmrc::Config| {
+            find_registry_entry_json(
+                npm_config,
+                &http::Client::new(npm_config)?,
+                registry.url(),
+                "@babel/core",
+                PackumentFormat::Abbreviated,
+            )
+        };
+
+        // Fresh entries are used without a request.
+        let npm_config = testing::get_npm_config("");
+        assert_eq!(
+            find_entry_json(&npm_config)?.unwrap()["name"],
+            "@babel/core"
+        );
+        assert_eq!(
+            find_entry_json(&npm_config)?.unwrap()["name"],
+            "@babel/core"
+        );
+        assert_eq!(registry.requests(), vec!["/@babel%2fcore"]);
+
+        // Stale entries are revalidated with the cached validators.
+        let npm_config = testing::get_npm_config("cache-min=0\n");
+        assert_eq!(
+            find_entry_json(&npm_config)?.unwrap()["name"],
+            "@babel/core"
+        );
+        let requests = registry.get_requests();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].headers.contains_key("if-none-match"));
+        assert_eq!(requests[1].headers["if-none-match"], "\"v1\"");
+        assert_eq!(
+            requests[1].headers["if-modified-since"],
+            "Tue, 15 Dec 2020 10:00:00 GMT"
+        );
+
+        // The revalidated entry is fresh again.
+        let cache = cache::Cache::new(&testing::get_npm_config(""))?;
+        let entry = cache
+            .get(registry.url(), "@babel/core", PackumentFormat::Abbreviated)
+            .unwrap();
+        assert!(cache.is_fresh(&entry));
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+
+        let npm_config = testing::get_npm_config("prefer-online=true\n");
+        assert_eq!(
+            find_entry_json(&npm_config)?.unwrap()["name"],
+            "@babel/core"
+        );
+        assert_eq!(registry.requests().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_registry_entry_json_removed() -> Result<()> {
+        let registry = testing::Server::new(
+            "127.0.0.1",
+            vec![
+                ("/@babel%2fcore", 200, get_core_packument()),
+                ("/@babel%2fcore", 404, b"{}".to_vec()),
+            ],
+        );
+        let npm_config = testing::get_npm_config("prefer-online=true\n");
+        let find_entry_json = || {
+            find_registry_entry_json(
+                &npm_config,
+                &http::Client::new(&npm_config)?,
+                registry.url(),
+                "@babel/core",
+                PackumentFormat::Abbreviated,
+            )
+        };
+        let cache = cache::Cache::new(&npm_config)?;
+
+        assert!(find_entry_json()?.is_some());
+        assert!(cache
+            .get(registry.url(), "@babel/core", PackumentFormat::Abbreviated)
+            .is_some());
+
+        assert!(find_entry_json()?.is_none());
+        assert!(cache
+            .get(registry.url(), "@babel/core", PackumentFormat::Abbreviated)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_name() {
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config("")));
+        assert_eq!(extension.name(), "js");
+    }
+
+    #[test]
+    fn test_registries() {
+        let (extension, _, _) = get_extension();
+        assert_eq!(extension.registries(), vec!["127.0.0.1", "localhost"]);
+
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config("")));
+        assert_eq!(extension.registries(), vec!["npmjs.com"]);
+    }
+
+    #[test]
+    fn test_identify_package_dependencies_scoped() -> Result<()> {
+        let (extension, default_registry, _) = get_extension();
+        let package_dependencies =
+            extension.identify_package_dependencies("@babel/core", &Some("7.12.3"), &vec![])?;
+        assert_eq!(package_dependencies.len(), 1);
+        let package_dependencies = &package_dependencies[0];
+        assert_eq!(
+            package_dependencies.package_version,
+            Ok("7.12.3".to_string())
+        );
+        assert_eq!(package_dependencies.registry_host_name, "localhost");
+        assert_eq!(
+            package_dependencies.dependencies,
+            vec![vouch_lib::extension::Dependency {
+                name: "@babel/types".to_string(),
+                version: Ok("7.12.10".to_string()),
+            }]
+        );
+        // Scoped packages are fetched from the scope registry only.
+        assert!(default_registry.requests().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_registries_package_metadata_scoped() -> Result<()> {
+        let (extension, default_registry, scope_registry) = get_extension();
+        let metadata = extension.registries_package_metadata("@babel/core", &Some("^7.12.0"))?;
+        assert_eq!(metadata.len(), 1);
+
+        // The latest version satisfies the range.
+        assert_eq!(metadata[0].registry_host_name, "localhost");
+        assert!(metadata[0].is_primary);
+        assert_eq!(metadata[0].package_version, "7.12.10");
+        assert_eq!(
+            metadata[0].human_url,
+            format!("{}@babel%2fcore/7.12.10", scope_registry.url())
+        );
+        assert_eq!(
+            metadata[0].artifact_url,
+            format!("{}@babel/core/-/core-7.12.10.tgz", scope_registry.url())
+        );
+
+        // Scoped package names are not sent to the default registry.
+        assert!(default_registry.requests().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_registries_packages_metadata() -> Result<()> {
+        let (extension, _default_registry, scope_registry) = get_extension();
+        let all_package_metadata = extension.registries_packages_metadata(&[
+            ("@babel/core", Some("^7.12.0")),
+            ("@babel/types", None),
+            ("@babel/core", Some("7.12.3")),
+            ("@babel/core", Some("^8.0.0")),
+        ]);
+        assert_eq!(all_package_metadata.len(), 4);
+
+        let get_versions = |package_metadata: &Result<PackageMetadata>| -> Vec<String> {
+            package_metadata
+                .as_ref()
+                .unwrap()
+                .registries
+                .iter()
+                .map(|metadata| metadata.package_version.clone())
+                .collect()
+        };
+        assert_eq!(get_versions(&all_package_metadata[0]), vec!["7.12.10"]);
+        assert_eq!(get_versions(&all_package_metadata[1]), vec!["7.12.10"]);
+        assert_eq!(get_versions(&all_package_metadata[2]), vec!["7.12.3"]);
+        assert!(all_package_metadata[3].is_err());
+
+        // The scope registry publishes no signing keys.
+        assert_eq!(
+            all_package_metadata[0].as_ref().unwrap().verification,
+            PackageVerification {
+                signatures: SignatureStatus::Unsupported,
+            }
+        );
+
+        // Each registry entry is fetched once.
+        let core_requests = scope_registry
+            .requests()
+            .into_iter()
+            .filter(|path| path == "/@babel%2fcore")
+            .count();
+        assert_eq!(core_requests, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_registries_packages_metadata_mirrors() -> Result<()> {
+        let packument = serde_json::to_vec(&serde_json::json!({
+            "name": "d3",
+            "dist-tags": {"latest": "6.5.0"},
+            "versions": {"6.5.0": {"name": "d3", "version": "6.5.0"}}
+        }))?;
+        let default_registry = testing::Server::new(
+            "127.0.0.1",
+            vec![("/d3", 200, packument.clone()), ("/left-pad", 500, vec![])],
+        );
+        let mirror_registry = testing::Server::new("localhost", vec![("/d3", 200, packument)]);
+        let failing_mirror_registry = testing::Server::new(
+            "127.0.0.1",
+            vec![("/d3", 500, vec![]), ("/left-pad", 500, vec![])],
+        );
+        let scope_registry = testing::Server::new(
+            "localhost",
+            vec![("/@babel%2fcore", 200, get_core_packument())],
+        );
+        let npm_config = testing::get_npm_config(&format!(
+            "registry={}\nmirror-registries={}, {}\n@babel:registry={}\nfetch-retries=0\n",
+            default_registry.url(),
+            mirror_registry.url(),
+            failing_mirror_registry.url(),
+            scope_registry.url()
+        ));
+        let extension = JsExtension::from_npm_config(Ok(npm_config));
+        let all_package_metadata = extension.registries_packages_metadata(&[
+            ("d3", None),
+            ("@babel/core", None),
+            ("left-pad", None),
+        ]);
+
+        // Mirrors provide unscoped packages. Failing mirrors are reported.
+        let package_metadata = all_package_metadata[0].as_ref().unwrap();
+        let registry_host_names: Vec<_> = package_metadata
+            .registries
+            .iter()
+            .map(|metadata| (metadata.registry_host_name.as_str(), metadata.is_primary))
+            .collect();
+        assert_eq!(
+            registry_host_names,
+            vec![("127.0.0.1", true), ("localhost", false)]
+        );
+        assert_eq!(package_metadata.registry_errors.len(), 1);
+        assert_eq!(
+            &package_metadata.registry_errors[0].registry_url,
+            failing_mirror_registry.url()
+        );
+
+        // Scoped packages are only requested from their scope registry.
+        let package_metadata = all_package_metadata[1].as_ref().unwrap();
+        assert_eq!(package_metadata.registries.len(), 1);
+        assert!(package_metadata.registry_errors.is_empty());
+        for registry in &[
+            &default_registry,
+            &mirror_registry,
+            &failing_mirror_registry,
+        ] {
+            assert!(!registry.requests().contains(&"/@babel%2fcore".to_string()));
+        }
+        assert!(!scope_registry.requests().contains(&"/d3".to_string()));
+
+        // A failing primary registry is an error.
+        assert!(all_package_metadata[2].is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_identify_file_defined_dependencies_scoped() -> Result<()> {
+        let project_directory = tempdir::TempDir::new("vouch_js_lib")?;
+        std::fs::write(
+            project_directory.path().join("package-lock.json"),
+            serde_json::to_vec(&serde_json::json!({
+                "lockfileVersion": 3,
+                "packages": {
+                    "": {"dependencies": {"@babel/core": "^7.12.0", "d3": "^6.5.0"}},
+                    "node_modules/@babel/core": {"version": "7.12.3"},
+                    "node_modules/@babel/core/node_modules/@babel/types": {"version": "7.12.1"},
+                    "node_modules/@types/node": {"version": "14.14.0", "dev": true},
+                    "node_modules/d3": {"version": "6.5.0"}
+                }
+            }))?,
+        )?;
+        let working_directory = project_directory.path().to_path_buf();
+        let get_dependency = |name: &str, version: &str| vouch_lib::extension::Dependency {
+            name: name.to_string(),
+            version: Ok(version.to_string()),
+        };
+
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config("")));
+        let file_defined_dependencies =
+            extension.identify_file_defined_dependencies(&working_directory, &vec![])?;
+        assert_eq!(file_defined_dependencies.len(), 1);
+        assert_eq!(file_defined_dependencies[0].registry_host_name, "npmjs.com");
+        assert_eq!(
+            file_defined_dependencies[0].dependencies,
+            vec![
+                get_dependency("@babel/core", "7.12.3"),
+                get_dependency("@babel/types", "7.12.1"),
+                get_dependency("d3", "6.5.0"),
+            ]
+        );
+
+        // Dependencies are reported under the registry configured for their scope.
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config(
+            "registry=https://registry.example.com/\n@babel:registry=https://npm.example.com/\n",
+        )));
+        let file_defined_dependencies =
+            extension.identify_file_defined_dependencies(&working_directory, &vec![])?;
+        let registries_dependencies: Vec<_> = file_defined_dependencies
+            .into_iter()
+            .map(|dependencies| (dependencies.registry_host_name, dependencies.dependencies))
+            .collect();
+        assert_eq!(
+            registries_dependencies,
+            vec![
+                (
+                    "npm.example.com".to_string(),
+                    vec![
+                        get_dependency("@babel/core", "7.12.3"),
+                        get_dependency("@babel/types", "7.12.1"),
+                    ]
+                ),
+                (
+                    "registry.example.com".to_string(),
+                    vec![get_dependency("d3", "6.5.0")]
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_npm_config_error() -> Result<()> {
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config(
+            "@babel:registry=not a url\n",
+        )));
+        assert!(extension.registries().is_empty());
+        let error = extension
+            .registries_package_metadata("@babel/core", &None)
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("Failed to load npm configuration"));
+
+        // Invalid HTTP client options are reported rather than panicking.
+        let extension =
+            JsExtension::from_npm_config(Ok(testing::get_npm_config("fetch-retries=many\n")));
+        let error = extension
+            .registries_package_metadata("d3", &None)
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid npm config fetch-retries value: many"));
+
+        let extension = JsExtension::from_npm_config(Err(format_err!("Failed to read npmrc.")));
+        let error = extension
+            .identify_file_defined_dependencies(&std::env::temp_dir(), &vec![])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to load npm configuration: Failed to read npmrc."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_package_versions_resolved_versions() -> Result<()> {
+        let old_archive = get_archive(&[("index.js", "module.exports = 1;\n")]);
+        let new_archive = get_archive(&[("index.js", "module.exports = 2;\n")]);
+        let get_integrity = |archive: &[u8]| {
+            integrity::Hash {
+                algorithm: integrity::Algorithm::Sha512,
+                digest: integrity::Algorithm::Sha512.digest(archive),
+            }
+            .to_string()
+        };
+        let packument = serde_json::to_vec(&serde_json::json!({
+            "name": "d3",
+            "dist-tags": {"latest": "6.5.0"},
+            "versions": {
+                "6.4.0": {"dist": {"integrity": get_integrity(&old_archive)}},
+                "6.5.0": {"dist": {"integrity": get_integrity(&new_archive)}}
+            }
+        }))?;
+        let registry = testing::Server::new(
+            "127.0.0.1",
+            vec![
+                ("/d3", 200, packument),
+                ("/d3/-/d3-6.4.0.tgz", 200, old_archive),
+                ("/d3/-/d3-6.5.0.tgz", 200, new_archive),
+            ],
+        );
+        let extension = JsExtension::from_npm_config(Ok(testing::get_npm_config(&format!(
+            "registry={}\n",
+            registry.url()
+        ))));
+
+        let package_diff = extension.diff_package_versions("d3", "~6.4.0", "latest")?;
+        assert_eq!(package_diff.old_version, "6.4.0");
+        assert_eq!(package_diff.new_version, "6.5.0");
+        assert_eq!(package_diff.files.len(), 1);
+        assert_eq!(package_diff.files[0].change, FileChange::Modified);
+        Ok(())
+    }
 }
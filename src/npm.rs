@@ -1,12 +1,14 @@
-use anyhow::{Context, Result};
-use std::collections::HashSet;
+use anyhow::{format_err, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
 
 static HOST_NAME: &str = "npmjs.com";
 
 /// Parse and clean package version string.
 ///
 /// Returns a structure which details common errors.
-fn get_parsed_version(version: &Option<&str>) -> vouch_lib::extension::common::VersionParseResult {
+pub fn get_parsed_version(
+    version: &Option<&str>,
+) -> vouch_lib::extension::common::VersionParseResult {
     if let Some(version) = version.and_then(|v| Some(v.to_string())) {
         if version != "" {
             return Ok(version);
@@ -17,10 +19,70 @@ fn get_parsed_version(version: &Option<&str>) -> vouch_lib::extension::common::V
 
 type JsonObject = serde_json::Map<String, serde_json::Value>;
 
+/// Dependencies parsed from a dependency definition file.
+#[derive(Debug, Clone, Default)]
+pub struct FileDependencies {
+    /// Packages fetched from a package registry.
+    pub registry_dependencies: BTreeSet<vouch_lib::extension::Dependency>,
+
+    /// Packages which are not fetched from a package registry, by resolution protocol.
+    ///
+    /// For example: `workspace`, `patch`, `git`, `file` or `tarball`.
+    pub excluded_dependencies: BTreeMap<String, BTreeSet<vouch_lib::extension::Dependency>>,
+}
+
+impl FileDependencies {
+    /// Add a dependency given its resolution protocol, or `None` for registry packages.
+    pub fn insert(
+        &mut self,
+        protocol: Option<String>,
+        dependency: vouch_lib::extension::Dependency,
+    ) {
+        match protocol {
+            Some(protocol) => {
+                self.excluded_dependencies
+                    .entry(protocol)
+                    .or_default()
+                    .insert(dependency);
+            }
+            None => {
+                self.registry_dependencies.insert(dependency);
+            }
+        }
+    }
+}
+
+/// Installed package entry of an npm lockfile.
+#[derive(Debug, Clone)]
+pub struct LockfileEntry {
+    pub name: String,
+    pub version: Option<String>,
+    pub resolved: Option<String>,
+    pub integrity: Option<String>,
+
+    /// True if the package is bundled within its dependent's archive.
+    pub is_bundled: bool,
+}
+
+impl LockfileEntry {
+    fn new(name: String, entry: &serde_json::Value) -> Self {
+        let get_string = |field: &str| entry[field].as_str().map(|v| v.to_string());
+        Self {
+            name,
+            version: get_string("version"),
+            resolved: get_string("resolved"),
+            integrity: get_string("integrity"),
+            is_bundled: entry["inBundle"].as_bool().unwrap_or_default()
+                || entry["bundled"].as_bool().unwrap_or_default(),
+        }
+    }
+}
+
+/// Parse entries from the legacy nested `dependencies` section (lockfileVersion 1).
 fn parse_dependencies(
     package_entry: &serde_json::Value,
     include_dev_dependencies: bool,
-) -> Result<Vec<vouch_lib::extension::Dependency>> {
+) -> Result<Vec<LockfileEntry>> {
     let mut unprocessed_dependencies_sections: std::collections::VecDeque<&JsonObject> =
         std::collections::VecDeque::new();
 
@@ -28,46 +90,557 @@ fn parse_dependencies(
         unprocessed_dependencies_sections.push_back(dependencies);
     }
 
-    let mut all_dependencies = HashSet::new();
+    let mut all_entries = Vec::new();
     while let Some(dependencies) = unprocessed_dependencies_sections.pop_front() {
         for (package_name, entry) in dependencies {
             if !include_dev_dependencies && entry["dev"].as_bool().unwrap_or_default() {
                 continue;
             }
 
-            let version_parse_result = get_parsed_version(&entry["version"].as_str());
-            all_dependencies.insert(vouch_lib::extension::Dependency {
-                name: package_name.clone(),
-                version: version_parse_result,
-            });
+            all_entries.push(LockfileEntry::new(package_name.clone(), entry));
 
             if let Some(sub_dependencies) = entry["dependencies"].as_object() {
                 unprocessed_dependencies_sections.push_back(sub_dependencies);
             }
         }
     }
+    Ok(all_entries)
+}
 
-    let mut all_dependencies: Vec<_> = all_dependencies.into_iter().collect();
-    all_dependencies.sort();
-    Ok(all_dependencies)
+/// Derive package name from a `packages` section install path.
+///
+/// Example: `node_modules/a/node_modules/@scope/b` yields `@scope/b`.
+fn get_package_name_from_path(path: &str) -> Option<String> {
+    let (_, name) = path.rsplit_once("node_modules/")?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Parse entries from the flat `packages` section (lockfileVersion 2 and 3).
+fn parse_packages(
+    packages: &JsonObject,
+    include_dev_dependencies: bool,
+) -> Result<Vec<LockfileEntry>> {
+    let mut all_entries = Vec::new();
+    for (path, entry) in packages {
+        // The root project is keyed by the empty path. Workspace members are
+        // stored outside of node_modules and linked into it.
+        let path_name = match get_package_name_from_path(path) {
+            Some(v) => v,
+            None => continue,
+        };
+        if entry["link"].as_bool().unwrap_or_default() {
+            continue;
+        }
+        // Optional and peer dependencies are installed by default. `devOptional`
+        // entries are optional dependencies of non-dev packages, so they are kept too.
+        if !include_dev_dependencies && entry["dev"].as_bool().unwrap_or_default() {
+            continue;
+        }
+
+        // Aliased packages (`npm:` specifiers) record their real name.
+        let package_name = entry["name"]
+            .as_str()
+            .map(|v| v.to_string())
+            .unwrap_or(path_name);
+
+        all_entries.push(LockfileEntry::new(package_name, entry));
+    }
+    Ok(all_entries)
+}
+
+/// Parse installed package entries from an npm lockfile.
+///
+/// Supports both package-lock.json and npm-shrinkwrap.json, which share a format.
+/// Lockfile versions 2 and 3 are parsed using the `packages` section. Earlier
+/// versions fall back to the nested `dependencies` section.
+pub fn get_lockfile_entries(
+    file_path: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<Vec<LockfileEntry>> {
+    let file = std::fs::File::open(file_path)?;
+    let reader = std::io::BufReader::new(file);
+    let package_entry: serde_json::Value = serde_json::from_reader(reader).context(format!(
+        "Failed to parse npm lockfile: {}",
+        file_path.display()
+    ))?;
+
+    let lockfile_version = package_entry["lockfileVersion"].as_u64().unwrap_or(1);
+    match package_entry["packages"].as_object() {
+        Some(packages) if lockfile_version >= 2 => {
+            parse_packages(packages, include_dev_dependencies)
+        }
+        _ => parse_dependencies(&package_entry, include_dev_dependencies),
+    }
+}
+
+/// Returns the resolution protocol of a lockfile entry which is not fetched from a registry.
+///
+/// Example: `git+ssh://git@github.com/owner/repo.git#<commit>` yields `git`. Returns `None`
+/// for registry versions and archive URLs.
+fn get_non_registry_protocol(entry: &LockfileEntry) -> Option<String> {
+    // Lockfile version 1 records non-registry sources in the version field.
+    let reference = entry.resolved.as_deref().or(entry.version.as_deref())?;
+    let (scheme, _) = reference.split_once(':')?;
+    match scheme {
+        "http" | "https" => None,
+        _ => Some(scheme.split('+').next().unwrap_or(scheme).to_string()),
+    }
 }
 
 /// Parse dependencies from project dependencies definition file.
+///
+/// Packages which are not fetched from a registry (`git`, `file`, ...) are excluded by
+/// resolution protocol.
 pub fn get_dependencies(
     file_path: &std::path::PathBuf,
     include_dev_dependencies: bool,
-) -> Result<Vec<vouch_lib::extension::Dependency>> {
+) -> Result<FileDependencies> {
+    let mut all_dependencies = FileDependencies::default();
+    for entry in get_lockfile_entries(file_path, include_dev_dependencies)? {
+        all_dependencies.insert(
+            get_non_registry_protocol(&entry),
+            vouch_lib::extension::Dependency {
+                version: get_parsed_version(&entry.version.as_deref()),
+                name: entry.name,
+            },
+        );
+    }
+    Ok(all_dependencies)
+}
+
+/// Split a package specifier into name and range components.
+///
+/// Example: `@babel/core@^7.0.0` yields `("@babel/core", "^7.0.0")`.
+pub fn split_specifier(specifier: &str) -> Option<(&str, &str)> {
+    let separator_index = specifier.get(1..)?.find('@')? + 1;
+    Some((
+        &specifier[..separator_index],
+        &specifier[separator_index + 1..],
+    ))
+}
+
+/// Error reported by a failed npm command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NpmError {
+    /// E404: package or version not found in the registry.
+    NotFound { package: String, details: String },
+    /// ERESOLVE: conflicting dependency requirements.
+    Unresolvable { package: String, details: String },
+    /// ENOTFOUND: registry host name could not be resolved.
+    RegistryUnreachable { details: String },
+    /// EINTEGRITY: downloaded data did not match the expected checksum.
+    IntegrityMismatch { details: String },
+    /// ENOTCACHED: offline mode is enabled and the package is missing from npm's cache.
+    NotCached { package: String, details: String },
+    /// Any other npm failure.
+    Other {
+        code: Option<String>,
+        exit_code: Option<i32>,
+        details: String,
+    },
+}
+
+impl NpmError {
+    /// Parse npm's stderr output into an error.
+    pub fn from_output(package: &str, exit_code: Option<i32>, stderr: &str) -> Self {
+        let mut code = None;
+        let mut details = Vec::new();
+        for line in stderr.lines() {
+            // npm 10 uses the `npm error` prefix, earlier versions use `npm ERR!`.
+            let line = match line
+                .strip_prefix("npm ERR!")
+                .or_else(|| line.strip_prefix("npm error"))
+            {
+                Some(v) => v.trim(),
+                None => continue,
+            };
+            if let Some(error_code) = line.strip_prefix("code ") {
+                code = Some(error_code.trim().to_string());
+            } else if !line.is_empty()
+                && !line.starts_with("errno ")
+                && !line.starts_with("syscall ")
+                && !line.starts_with("A complete log of this run can be found in")
+            {
+                details.push(line);
+            }
+        }
+        let details = details.join("\n");
+
+        match code.as_deref() {
+            Some("E404") => Self::NotFound {
+                package: package.to_string(),
+                details,
+            },
+            Some("ERESOLVE") => Self::Unresolvable {
+                package: package.to_string(),
+                details,
+            },
+            Some("ENOTFOUND") => Self::RegistryUnreachable { details },
+            Some("EINTEGRITY") => Self::IntegrityMismatch { details },
+            Some("ENOTCACHED") => Self::NotCached {
+                package: package.to_string(),
+                details,
+            },
+            _ => Self::Other {
+                code,
+                exit_code,
+                details: if details.is_empty() {
+                    stderr.trim().to_string()
+                } else {
+                    details
+                },
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for NpmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound { package, details } => write!(
+                f,
+                "Package {} was not found in the npm registry. \
+                Check the package name and version.\n{}",
+                package, details
+            ),
+            Self::Unresolvable { package, details } => write!(
+                f,
+                "npm could not resolve a dependency tree for {} due to conflicting \
+                dependency requirements. Try a different package version.\n{}",
+                package, details
+            ),
+            Self::RegistryUnreachable { details } => write!(
+                f,
+                "npm could not reach the package registry. \
+                Check your network connection and registry configuration.\n{}",
+                details
+            ),
+            Self::IntegrityMismatch { details } => write!(
+                f,
+                "npm rejected downloaded package data which did not match its expected \
+                checksum. Run `npm cache verify` and try again.\n{}",
+                details
+            ),
+            Self::NotCached { package, details } => write!(
+                f,
+                "Package {} or one of its dependencies is not available in npm's cache. \
+                Disable npm's offline option to fetch it from the registry.\n{}",
+                package, details
+            ),
+            Self::Other {
+                code,
+                exit_code,
+                details,
+            } => write!(
+                f,
+                "npm failed (code: {}, exit status: {}).\n{}",
+                code.as_deref().unwrap_or("unknown"),
+                exit_code
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                details
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NpmError {}
+
+/// Returns the version of a top level package installed according to a lockfile.
+fn get_installed_version(
+    file_path: &std::path::PathBuf,
+    package_name: &str,
+) -> Result<Option<String>> {
     let file = std::fs::File::open(file_path)?;
     let reader = std::io::BufReader::new(file);
     let package_entry: serde_json::Value = serde_json::from_reader(reader).context(format!(
-        "Failed to parse package-lock.json: {}",
+        "Failed to parse npm lockfile: {}",
         file_path.display()
     ))?;
 
-    let all_dependencies = parse_dependencies(&package_entry, include_dev_dependencies)?;
-    Ok(all_dependencies)
+    let package_path = format!("node_modules/{}", package_name);
+    let version = package_entry["packages"][package_path]["version"]
+        .as_str()
+        .or_else(|| package_entry["dependencies"][package_name]["version"].as_str());
+    Ok(version.map(|v| v.to_string()))
+}
+
+/// Returns a list of dependencies for the given package using npm.
+///
+/// Generates a package-lock.json file for the package using npm in a temporary directory.
+/// In offline mode, npm resolves the package using its cache only.
+pub fn identify_package_dependencies(
+    package_name: &str,
+    package_version: &Option<&str>,
+    is_offline: bool,
+) -> Result<(
+    vouch_lib::extension::VersionParseResult,
+    Vec<vouch_lib::extension::Dependency>,
+)> {
+    // npm install is-even@1.0.0 --package-lock-only
+    let tmp_dir = tempdir::TempDir::new("vouch_js_identify_package_dependencies")?;
+    let tmp_directory_path = tmp_dir.path().to_path_buf();
+
+    let package = if let Some(package_version) = package_version {
+        format!(
+            "{name}@{version}",
+            name = package_name,
+            version = package_version
+        )
+    } else {
+        package_name.to_string()
+    };
+    let mut args = vec!["install", package.as_str(), "--package-lock-only"];
+    if is_offline {
+        args.push("--offline");
+    }
+
+    let output = std::process::Command::new("npm")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .current_dir(&tmp_directory_path)
+        .output()
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => format_err!(
+                "Failed to find npm. Install Node.js and npm, \
+                or omit the --npm-resolver argument to use the native resolver."
+            ),
+            _ => format_err!("Failed to run npm: {}", error),
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(NpmError::from_output(&package, output.status.code(), &stderr).into());
+    }
+
+    let package_lock_path = tmp_directory_path.join("package-lock.json");
+    let dependencies = get_dependencies(&package_lock_path, false)?;
+
+    // The package version argument may be a range or dist-tag. Report the installed version.
+    let package_version =
+        get_parsed_version(&get_installed_version(&package_lock_path, package_name)?.as_deref());
+
+    let dependencies = dependencies
+        .registry_dependencies
+        .into_iter()
+        .filter(|d| !(d.name == package_name && d.version == package_version))
+        .collect();
+    Ok((package_version, dependencies))
 }
 
 pub fn get_registry_host_name() -> String {
     HOST_NAME.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_names_versions(entries: &[LockfileEntry]) -> Vec<(&str, &str)> {
+        entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.name.as_str(),
+                    entry.version.as_deref().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    fn write_lockfile(lockfile: &serde_json::Value) -> Result<tempdir::TempDir> {
+        let project_directory = tempdir::TempDir::new("vouch_js_npm")?;
+        std::fs::write(
+            project_directory.path().join("package-lock.json"),
+            serde_json::to_vec(lockfile)?,
+        )?;
+        Ok(project_directory)
+    }
+
+    #[test]
+    fn test_parse_packages() -> Result<()> {
+        let lockfile = serde_json::json!({
+            "": {"name": "my-project", "workspaces": ["packages/utils"]},
+            "packages/utils": {"name": "@scope/utils", "version": "1.0.0"},
+            "node_modules/@scope/utils": {"resolved": "packages/utils", "link": true},
+            "node_modules/string-width-cjs": {"name": "string-width", "version": "4.2.3"},
+            "node_modules/fsevents": {"version": "2.3.2", "optional": true},
+            "node_modules/react": {"version": "17.0.2", "peer": true},
+            "node_modules/jest": {"version": "26.6.3", "dev": true},
+            "node_modules/jest/node_modules/@babel/core": {"version": "7.12.3", "dev": true},
+            "node_modules/chokidar": {"version": "3.5.1", "devOptional": true},
+            "node_modules/d3/node_modules/d3-array": {
+                "version": "2.12.1",
+                "inBundle": true
+            }
+        });
+        let packages = lockfile.as_object().unwrap();
+
+        let entries = parse_packages(packages, false)?;
+        assert_eq!(
+            get_names_versions(&entries),
+            vec![
+                ("chokidar", "3.5.1"),
+                ("d3-array", "2.12.1"),
+                ("fsevents", "2.3.2"),
+                ("react", "17.0.2"),
+                ("string-width", "4.2.3"),
+            ]
+        );
+        assert!(entries[1].is_bundled);
+
+        let entries = parse_packages(packages, true)?;
+        assert_eq!(
+            get_names_versions(&entries),
+            vec![
+                ("chokidar", "3.5.1"),
+                ("d3-array", "2.12.1"),
+                ("fsevents", "2.3.2"),
+                ("jest", "26.6.3"),
+                ("@babel/core", "7.12.3"),
+                ("react", "17.0.2"),
+                ("string-width", "4.2.3"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_lockfile_entries_version_1() -> Result<()> {
+        let project_directory = write_lockfile(&serde_json::json!({
+            "lockfileVersion": 1,
+            "dependencies": {
+                "d3": {
+                    "version": "6.5.0",
+                    "dependencies": {"d3-array": {"version": "2.12.1"}}
+                },
+                "jest": {"version": "26.6.3", "dev": true},
+                "string-width-cjs": {"version": "npm:string-width@4.2.3"}
+            }
+        }))?;
+        let file_path = project_directory.path().join("package-lock.json");
+
+        let entries = get_lockfile_entries(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&entries),
+            vec![
+                ("d3", "6.5.0"),
+                ("string-width-cjs", "npm:string-width@4.2.3"),
+                ("d3-array", "2.12.1"),
+            ]
+        );
+        assert_eq!(get_lockfile_entries(&file_path, true)?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_lockfile_entries_version_2() -> Result<()> {
+        // Version 2 lockfiles record both sections. The `packages` section is used.
+        let project_directory = write_lockfile(&serde_json::json!({
+            "lockfileVersion": 2,
+            "packages": {"node_modules/d3": {"version": "6.5.0"}},
+            "dependencies": {"d3": {"version": "6.4.0"}}
+        }))?;
+        let file_path = project_directory.path().join("package-lock.json");
+        let entries = get_lockfile_entries(&file_path, false)?;
+        assert_eq!(get_names_versions(&entries), vec![("d3", "6.5.0")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_npm_error_from_output() {
+        let details_404 =
+            "404 Not Found - GET https://registry.npmjs.org/no-such-package - Not found\n\
+            404\n\
+            404  'no-such-package@1.0.0' is not in this registry.";
+        let details_eresolve = "ERESOLVE unable to resolve dependency tree\n\
+            Found: react@17.0.2\n\
+            Could not resolve dependency:\n\
+            peer react@\"^16.0.0\" from react-dom@16.14.0";
+        let details_enotfound = "request to https://registry.example.com/d3 failed, \
+            reason: getaddrinfo ENOTFOUND registry.example.com";
+        let details_eintegrity = "sha512-AAAA integrity checksum failed when using sha512: \
+            wanted sha512-AAAA but got sha512-BBBB. (1024 bytes)";
+        let cases = vec![
+            (
+                "E404",
+                details_404,
+                NpmError::NotFound {
+                    package: "d3@6.5.0".to_string(),
+                    details: details_404.to_string(),
+                },
+            ),
+            (
+                "ERESOLVE",
+                details_eresolve,
+                NpmError::Unresolvable {
+                    package: "d3@6.5.0".to_string(),
+                    details: details_eresolve.to_string(),
+                },
+            ),
+            (
+                "ENOTFOUND",
+                details_enotfound,
+                NpmError::RegistryUnreachable {
+                    details: details_enotfound.to_string(),
+                },
+            ),
+            (
+                "EINTEGRITY",
+                details_eintegrity,
+                NpmError::IntegrityMismatch {
+                    details: details_eintegrity.to_string(),
+                },
+            ),
+        ];
+
+        // npm 9 and earlier use the `npm ERR!` prefix, npm 10 uses `npm error`.
+        for prefix in &["npm ERR!", "npm error"] {
+            for (code, details, expected_error) in &cases {
+                let mut stderr = format!("{} code {}\n{} errno -3008\n", prefix, code, prefix);
+                stderr.push_str(&format!("{} syscall getaddrinfo\n", prefix));
+                for line in details.lines() {
+                    stderr.push_str(&format!("{} {}\n", prefix, line));
+                }
+                stderr.push_str(&format!(
+                    "{}\n{} A complete log of this run can be found in: /root/.npm/_logs/debug.log\n",
+                    prefix, prefix
+                ));
+                assert_eq!(
+                    &NpmError::from_output("d3@6.5.0", Some(1), &stderr),
+                    expected_error,
+                    "{}",
+                    stderr
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_npm_error_from_output_other() {
+        let stderr = "npm error code EACCES\nnpm error permission denied\n";
+        assert_eq!(
+            NpmError::from_output("d3", Some(243), stderr),
+            NpmError::Other {
+                code: Some("EACCES".to_string()),
+                exit_code: Some(243),
+                details: "permission denied".to_string(),
+            }
+        );
+
+        // Output without npm's error prefix is reported as is.
+        let stderr = "Segmentation fault\n";
+        assert_eq!(
+            NpmError::from_output("d3", None, stderr),
+            NpmError::Other {
+                code: None,
+                exit_code: None,
+                details: "Segmentation fault".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,658 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::HashMap;
+
+use crate::npm;
+
+/// Default npm registry URL.
+static DEFAULT_REGISTRY_URL: &str = "https://registry.npmjs.com/";
+
+/// Host names of the default npm registry.
+pub static DEFAULT_REGISTRY_HOST_NAMES: &[&str] = &["registry.npmjs.com", "registry.npmjs.org"];
+
+/// Environment variable prefix for npm configuration values.
+static ENVIRONMENT_PREFIX: &str = "npm_config_";
+
+/// Configuration key suffixes which hold credentials. These values are never displayed.
+static CREDENTIAL_KEYS: &[&str] = &["_authToken", "_auth", "_password", "password"];
+
+/// npm configuration read from .npmrc files and the environment.
+///
+/// Configuration sources in order of increasing precedence: global npmrc, user
+/// .npmrc, project .npmrc and `npm_config_*` environment variables.
+#[derive(Clone, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values: std::collections::BTreeMap<_, _> = self
+            .values
+            .iter()
+            .map(|(key, value)| {
+                if is_credential_key(key) {
+                    (key, "(redacted)")
+                } else {
+                    (key, value.as_str())
+                }
+            })
+            .collect();
+        f.debug_struct("Config").field("values", &values).finish()
+    }
+}
+
+impl Config {
+    /// Load configuration for the project containing the working directory.
+    pub fn load(working_directory: &std::path::Path) -> Result<Self> {
+        let environment = get_environment_values();
+
+        let mut config = Self::default();
+        let global_config_path = match environment.get("globalconfig") {
+            Some(path) => std::path::PathBuf::from(path),
+            None => get_global_prefix(&environment).join("etc").join("npmrc"),
+        };
+        config.read_file(&global_config_path)?;
+
+        let user_config_path = match environment.get("userconfig") {
+            Some(path) => Some(std::path::PathBuf::from(path)),
+            None => dirs::home_dir().map(|home| home.join(".npmrc")),
+        };
+        if let Some(user_config_path) = user_config_path {
+            config.read_file(&user_config_path)?;
+        }
+
+        if let Some(project_directory) = get_project_directory(working_directory) {
+            config.read_file(&project_directory.join(".npmrc"))?;
+        }
+
+        config.values.extend(environment);
+        Ok(config)
+    }
+
+    /// Returns configuration given ini formatted content.
+    #[cfg(test)]
+    pub fn from_content(content: &str) -> Result<Self> {
+        Ok(Self {
+            values: parse(content)?,
+        })
+    }
+
+    /// Read and merge values from an ini formatted file, if it exists.
+    fn read_file(&mut self, file_path: &std::path::Path) -> Result<()> {
+        if !file_path.is_file() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(file_path).context(format!(
+            "Failed to read npm config file: {}",
+            file_path.display()
+        ))?;
+        let values = parse(&content).context(format!(
+            "Failed to parse npm config file: {}",
+            file_path.display()
+        ))?;
+        self.values.extend(values);
+        Ok(())
+    }
+
+    /// Returns the configuration value for the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// Returns true if the boolean configuration value for the given key is set.
+    pub fn is_enabled(&self, key: &str) -> bool {
+        self.get(key).map(|v| v == "true").unwrap_or_default()
+    }
+
+    /// Returns true if registry requests are disabled with npm's `offline` option.
+    pub fn is_offline(&self) -> bool {
+        self.is_enabled("offline")
+    }
+
+    /// Returns the `@scope:registry` value configured for a scoped package, if any.
+    fn get_scope_registry(&self, package_name: &str) -> Option<&str> {
+        package_name
+            .split_once('/')
+            .filter(|(scope, _)| scope.starts_with('@'))
+            .and_then(|(scope, _)| self.get(&format!("{}:registry", scope)))
+    }
+
+    /// Returns the registry URL configured for a package.
+    ///
+    /// Scoped packages use the `@scope:registry` value if it is set.
+    pub fn get_registry_url(&self, package_name: &str) -> Result<url::Url> {
+        match self.get_scope_registry(package_name) {
+            Some(registry) => parse_registry_url(registry),
+            None => self.get_default_registry_url(),
+        }
+    }
+
+    /// Returns the URLs of registries which can serve a package. The registry npm would
+    /// install the package from is first.
+    ///
+    /// Packages mapped to a scope registry are only served by that registry. Other
+    /// packages are served by the default registry and its configured mirrors.
+    pub fn get_package_registry_urls(&self, package_name: &str) -> Result<Vec<url::Url>> {
+        match self.get_scope_registry(package_name) {
+            Some(registry) => Ok(vec![parse_registry_url(registry)?]),
+            None => self.get_default_registry_urls(),
+        }
+    }
+
+    /// Returns the default registry URL followed by the URLs of its mirrors, configured
+    /// with the comma separated `mirror-registries` option.
+    fn get_default_registry_urls(&self) -> Result<Vec<url::Url>> {
+        let mut registry_urls = vec![self.get_default_registry_url()?];
+        for registry in self.get("mirror-registries").unwrap_or_default().split(',') {
+            let registry = registry.trim();
+            if registry.is_empty() {
+                continue;
+            }
+            let registry_url = parse_registry_url(registry)?;
+            if !registry_urls.contains(&registry_url) {
+                registry_urls.push(registry_url);
+            }
+        }
+        Ok(registry_urls)
+    }
+
+    /// Returns the registry URL configured for unscoped packages.
+    pub fn get_default_registry_url(&self) -> Result<url::Url> {
+        parse_registry_url(self.get("registry").unwrap_or(DEFAULT_REGISTRY_URL))
+    }
+
+    /// Returns the credentials header value configured for a nerf-darted URL prefix.
+    ///
+    /// Example prefix: `//registry.example.com/path/`.
+    fn get_prefix_authorization(&self, prefix: &str) -> Result<Option<String>> {
+        let get = |key: &str| self.get(&format!("{}{}", prefix, key));
+        get_authorization_from_values(get)
+    }
+
+    /// Returns true if credentials should be sent with every request for the registry.
+    fn is_always_auth(&self, registry_url: &url::Url) -> bool {
+        self.get(&format!("{}:always-auth", get_nerf_dart(registry_url)))
+            .or_else(|| self.get("always-auth"))
+            .map(|v| v == "true")
+            .unwrap_or_default()
+    }
+
+    /// Returns the `Authorization` header value to send with a registry request.
+    ///
+    /// Credentials are matched against the request URL using npm's nerf-dart keys
+    /// (`//host/path/:_authToken`), walking up the URL path. Legacy unscoped
+    /// credentials apply to requests to the configured registry. If `always-auth` is
+    /// set, registry credentials are also sent to other hosts, such as tarball CDNs.
+    pub fn get_authorization(
+        &self,
+        url: &url::Url,
+        registry_url: &url::Url,
+    ) -> Result<Option<String>> {
+        for prefix in get_nerf_dart_prefixes(url) {
+            if let Some(authorization) = self.get_prefix_authorization(&prefix)? {
+                return Ok(Some(authorization));
+            }
+        }
+
+        let default_registry_url = self.get_default_registry_url()?;
+        let is_registry_request = url.as_str().starts_with(registry_url.as_str());
+        if is_registry_request && registry_url == &default_registry_url {
+            if let Some(authorization) = get_authorization_from_values(|key| self.get(key))? {
+                return Ok(Some(authorization));
+            }
+        }
+
+        if !is_registry_request && self.is_always_auth(registry_url) {
+            return self.get_authorization(registry_url, registry_url);
+        }
+        Ok(None)
+    }
+
+    /// Returns all configured registry URLs, starting with the default registry and its
+    /// mirrors.
+    pub fn get_registry_urls(&self) -> Result<Vec<url::Url>> {
+        let mut registry_urls = self.get_default_registry_urls()?;
+
+        let mut scope_registries: Vec<_> = self
+            .values
+            .iter()
+            .filter(|(key, _)| key.starts_with('@') && key.ends_with(":registry"))
+            .map(|(_, value)| value)
+            .collect();
+        scope_registries.sort();
+        for registry in scope_registries {
+            let registry_url = parse_registry_url(registry)?;
+            if !registry_urls.contains(&registry_url) {
+                registry_urls.push(registry_url);
+            }
+        }
+        Ok(registry_urls)
+    }
+}
+
+/// Parse a registry URL, ensuring that it ends with a path separator.
+fn parse_registry_url(registry: &str) -> Result<url::Url> {
+    let registry = if registry.ends_with('/') {
+        registry.to_string()
+    } else {
+        format!("{}/", registry)
+    };
+    url::Url::parse(&registry).context(format!("Invalid registry URL: {}", registry))
+}
+
+/// Returns true if the URL refers to the default npm registry.
+pub fn is_default_registry(registry_url: &url::Url) -> bool {
+    registry_url
+        .host_str()
+        .map(|host| DEFAULT_REGISTRY_HOST_NAMES.contains(&host))
+        .unwrap_or_default()
+}
+
+/// Returns the Vouch registry host name for a registry URL.
+///
+/// The default npm registry is identified by the npm registry host name.
+pub fn get_registry_host_name(registry_url: &url::Url) -> Result<String> {
+    if is_default_registry(registry_url) {
+        return Ok(npm::get_registry_host_name());
+    }
+    Ok(registry_url
+        .host_str()
+        .ok_or(format_err!("Registry URL has no host: {}", registry_url))?
+        .to_string())
+}
+
+/// Returns true if the configuration key holds credentials.
+fn is_credential_key(key: &str) -> bool {
+    let key = key.rsplit(':').next().unwrap_or(key);
+    CREDENTIAL_KEYS.contains(&key)
+}
+
+/// Returns an `Authorization` header value given a credentials lookup function.
+///
+/// Supports `_authToken` (bearer token), `_auth` (base64 encoded `username:password`)
+/// and `username` with `_password` (base64 encoded password).
+fn get_authorization_from_values<'a>(
+    get: impl Fn(&str) -> Option<&'a str>,
+) -> Result<Option<String>> {
+    if let Some(token) = get(":_authToken").or_else(|| get("_authToken")) {
+        return Ok(Some(format!("Bearer {}", token)));
+    }
+    if let Some(auth) = get(":_auth").or_else(|| get("_auth")) {
+        return Ok(Some(format!("Basic {}", auth)));
+    }
+    let username = get(":username").or_else(|| get("username"));
+    let password = get(":_password").or_else(|| get("_password"));
+    if let (Some(username), Some(password)) = (username, password) {
+        let password = base64::decode(password)
+            .ok()
+            .and_then(|password| String::from_utf8(password).ok())
+            .ok_or(format_err!(
+                "Failed to decode npm config _password value for user {}: expected base64.",
+                username
+            ))?;
+        let credentials = base64::encode(format!("{}:{}", username, password));
+        return Ok(Some(format!("Basic {}", credentials)));
+    }
+    Ok(None)
+}
+
+/// Returns the nerf-darted form of a URL: the URL without scheme, truncated to its
+/// directory. Example: `https://example.com/npm/pkg` yields `//example.com/npm/`.
+fn get_nerf_dart(url: &url::Url) -> String {
+    let mut nerf_dart = format!("//{}", url.host_str().unwrap_or_default());
+    if let Some(port) = url.port() {
+        nerf_dart.push_str(&format!(":{}", port));
+    }
+    let path = url.path();
+    nerf_dart.push_str(&path[..path.rfind('/').unwrap_or(0) + 1]);
+    if !nerf_dart.ends_with('/') {
+        nerf_dart.push('/');
+    }
+    nerf_dart
+}
+
+/// Returns nerf-darted URL prefixes from most to least specific.
+///
+/// Example: `https://example.com/npm/pkg` yields `//example.com/npm/` and `//example.com/`.
+fn get_nerf_dart_prefixes(url: &url::Url) -> Vec<String> {
+    let mut prefixes = vec![get_nerf_dart(url)];
+    loop {
+        let prefix = prefixes.last().expect("non-empty prefixes");
+        let trimmed_prefix = &prefix[..prefix.len() - 1];
+        match trimmed_prefix.rfind('/') {
+            Some(index) if index > 1 => prefixes.push(trimmed_prefix[..index + 1].to_string()),
+            _ => break,
+        }
+    }
+    prefixes
+}
+
+/// Replace `${NAME}` environment variable references in a configuration value.
+///
+/// References can be escaped with a backslash: `\${NAME}`.
+fn replace_environment_variables(value: &str) -> Result<String> {
+    let mut result = String::new();
+    let mut remaining = value;
+    while let Some(start) = remaining.find("${") {
+        if remaining[..start].ends_with('\\') {
+            result.push_str(&remaining[..start - 1]);
+            result.push_str("${");
+            remaining = &remaining[start + 2..];
+            continue;
+        }
+        let end = match remaining[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &remaining[start + 2..end];
+        let variable = std::env::var(name).map_err(|_| {
+            format_err!(
+                "Failed to replace environment variable in npm config: {}",
+                name
+            )
+        })?;
+        result.push_str(&remaining[..start]);
+        result.push_str(&variable);
+        remaining = &remaining[end + 1..];
+    }
+    result.push_str(remaining);
+    Ok(result)
+}
+
+/// Parse ini formatted npm configuration content.
+fn parse(content: &str) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (line, "true"),
+        };
+        let value = if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
+        {
+            &value[1..value.len() - 1]
+        } else {
+            value
+        };
+        values.insert(
+            replace_environment_variables(key)?,
+            replace_environment_variables(value)?,
+        );
+    }
+    Ok(values)
+}
+
+/// Returns configuration values set by `npm_config_*` environment variables.
+fn get_environment_values() -> HashMap<String, String> {
+    std::env::vars()
+        .filter_map(|(name, value)| Some((get_environment_key(&name)?, value)))
+        .collect()
+}
+
+/// Returns the configuration key set by an `npm_config_*` environment variable.
+///
+/// As with npm, underscores become dashes except for a leading underscore. Example:
+/// `npm_config_fetch_retries` sets `fetch-retries` and `npm_config__auth` sets `_auth`.
+fn get_environment_key(name: &str) -> Option<String> {
+    let is_config_variable = name
+        .get(..ENVIRONMENT_PREFIX.len())
+        .map(|prefix| prefix.eq_ignore_ascii_case(ENVIRONMENT_PREFIX))
+        .unwrap_or_default();
+    if !is_config_variable || name.len() == ENVIRONMENT_PREFIX.len() {
+        return None;
+    }
+    let key = name[ENVIRONMENT_PREFIX.len()..].to_lowercase();
+    let (leading_underscore, key) = match key.strip_prefix('_') {
+        Some(key) => ("_", key),
+        None => ("", key.as_str()),
+    };
+    Some(format!("{}{}", leading_underscore, key.replace('_', "-")))
+}
+
+/// Returns the npm global prefix directory.
+fn get_global_prefix(environment: &HashMap<String, String>) -> std::path::PathBuf {
+    if let Some(prefix) = environment.get("prefix") {
+        return std::path::PathBuf::from(prefix);
+    }
+    if cfg!(windows) {
+        if let Ok(app_data) = std::env::var("APPDATA") {
+            return std::path::PathBuf::from(app_data).join("npm");
+        }
+    }
+    std::path::PathBuf::from("/usr/local")
+}
+
+/// Returns the nearest ancestor directory containing package.json or node_modules.
+fn get_project_directory(working_directory: &std::path::Path) -> Option<std::path::PathBuf> {
+    working_directory
+        .ancestors()
+        .find(|directory| {
+            directory.join("package.json").is_file() || directory.join("node_modules").is_dir()
+        })
+        .map(|directory| directory.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_environment_key() {
+        assert_eq!(
+            get_environment_key("npm_config_fetch_retries"),
+            Some("fetch-retries".to_string())
+        );
+        assert_eq!(
+            get_environment_key("NPM_CONFIG_REGISTRY"),
+            Some("registry".to_string())
+        );
+        assert_eq!(
+            get_environment_key("npm_config__auth"),
+            Some("_auth".to_string())
+        );
+        assert_eq!(get_environment_key("npm_config_"), None);
+        assert_eq!(get_environment_key("npm_fetch_retries"), None);
+    }
+
+    #[test]
+    fn test_get_package_registry_urls() -> Result<()> {
+        let config = Config::from_content(
+            "registry=https://npm.example.com/\n\
+             mirror-registries=https://registry.npmjs.org, https://mirror.example.com/npm/\n\
+             @corp:registry=https://npm.corp.example.com/\n",
+        )?;
+        let get_urls = |package_name: &str| -> Result<Vec<String>> {
+            Ok(config
+                .get_package_registry_urls(package_name)?
+                .iter()
+                .map(|url| url.to_string())
+                .collect())
+        };
+        assert_eq!(
+            get_urls("@corp/secret")?,
+            vec!["https://npm.corp.example.com/"]
+        );
+        let default_registry_urls = vec![
+            "https://npm.example.com/",
+            "https://registry.npmjs.org/",
+            "https://mirror.example.com/npm/",
+        ];
+        assert_eq!(get_urls("d3")?, default_registry_urls);
+        assert_eq!(get_urls("@babel/core")?, default_registry_urls);
+        assert_eq!(config.get_registry_urls()?.len(), 4);
+        Ok(())
+    }
+
+    fn get_authorization(config: &Config, url: &str, registry_url: &str) -> Result<Option<String>> {
+        config.get_authorization(&url::Url::parse(url)?, &url::Url::parse(registry_url)?)
+    }
+
+    #[test]
+    fn test_get_authorization_nerf_dart() -> Result<()> {
+        let config = Config::from_content(
+            "//npm.example.com/api/npm/:_authToken=path-token\n\
+             //npm.example.com:8443/:_authToken=port-token\n",
+        )?;
+        let registry_url = "https://npm.example.com/api/npm/";
+        assert_eq!(
+            get_authorization(&config, "https://npm.example.com/api/npm/d3", registry_url)?,
+            Some("Bearer path-token".to_string())
+        );
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://npm.example.com/api/npm/d3/-/d3-6.5.0.tgz",
+                registry_url
+            )?,
+            Some("Bearer path-token".to_string())
+        );
+        assert_eq!(
+            get_authorization(&config, "https://npm.example.com/other/d3", registry_url)?,
+            None
+        );
+
+        // Ports are part of the nerf-dart.
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://npm.example.com:8443/d3",
+                "https://npm.example.com:8443/"
+            )?,
+            Some("Bearer port-token".to_string())
+        );
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://npm.example.com/d3",
+                "https://npm.example.com/"
+            )?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_authorization_legacy_credentials() -> Result<()> {
+        let config = Config::from_content(
+            "_authToken=legacy-token\n@corp:registry=https://npm.corp.example.com/\n",
+        )?;
+        let default_registry_url = "https://registry.npmjs.com/";
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://registry.npmjs.com/d3",
+                default_registry_url
+            )?,
+            Some("Bearer legacy-token".to_string())
+        );
+
+        // Legacy credentials are not sent to scope registries or other hosts.
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://npm.corp.example.com/@corp%2fsecret",
+                "https://npm.corp.example.com/"
+            )?,
+            None
+        );
+        assert_eq!(
+            get_authorization(
+                &config,
+                "https://cdn.example.com/d3-6.5.0.tgz",
+                default_registry_url
+            )?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_authorization_always_auth() -> Result<()> {
+        let registry_url = "https://npm.example.com/";
+        let tarball_url = "https://cdn.example.com/d3-6.5.0.tgz";
+
+        let config = Config::from_content("//npm.example.com/:_authToken=token\n")?;
+        assert_eq!(get_authorization(&config, tarball_url, registry_url)?, None);
+
+        for always_auth in &["//npm.example.com/:always-auth=true", "always-auth=true"] {
+            let config = Config::from_content(&format!(
+                "//npm.example.com/:_authToken=token\n{}\n",
+                always_auth
+            ))?;
+            assert_eq!(
+                get_authorization(&config, tarball_url, registry_url)?,
+                Some("Bearer token".to_string())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_authorization_basic() -> Result<()> {
+        let registry_url = "https://npm.example.com/";
+        let config = Config::from_content(&format!(
+            "//npm.example.com/:username=user\n//npm.example.com/:_password={}\n",
+            base64::encode("secret")
+        ))?;
+        assert_eq!(
+            get_authorization(&config, "https://npm.example.com/d3", registry_url)?,
+            Some(format!("Basic {}", base64::encode("user:secret")))
+        );
+
+        let config = Config::from_content("//npm.example.com/:_auth=dXNlcjpzZWNyZXQ=\n")?;
+        assert_eq!(
+            get_authorization(&config, "https://npm.example.com/d3", registry_url)?,
+            Some("Basic dXNlcjpzZWNyZXQ=".to_string())
+        );
+
+        let config = Config::from_content(
+            "//npm.example.com/:username=user\n//npm.example.com/:_password=not base64!\n",
+        )?;
+        assert!(get_authorization(&config, "https://npm.example.com/d3", registry_url).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_environment_variables() -> Result<()> {
+        std::env::set_var("VOUCH_JS_TEST_NPM_TOKEN", "secret-token");
+        assert_eq!(
+            replace_environment_variables("Bearer ${VOUCH_JS_TEST_NPM_TOKEN}!")?,
+            "Bearer secret-token!"
+        );
+        assert_eq!(
+            replace_environment_variables("\\${VOUCH_JS_TEST_NPM_TOKEN}")?,
+            "${VOUCH_JS_TEST_NPM_TOKEN}"
+        );
+        assert!(replace_environment_variables("${VOUCH_JS_TEST_MISSING_VARIABLE}").is_err());
+
+        let config =
+            Config::from_content("//npm.example.com/:_authToken=${VOUCH_JS_TEST_NPM_TOKEN}\n")?;
+        assert_eq!(
+            config.get("//npm.example.com/:_authToken"),
+            Some("secret-token")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacts_credentials() -> Result<()> {
+        let config = Config::from_content(
+            "registry=https://npm.example.com/\n\
+             //npm.example.com/:_authToken=secret-token\n\
+             //npm.example.com/:_password=c2VjcmV0\n\
+             _auth=dXNlcjpzZWNyZXQ=\n",
+        )?;
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("https://npm.example.com/"));
+        assert!(debug.contains("(redacted)"));
+        for secret in &["secret-token", "c2VjcmV0", "dXNlcjpzZWNyZXQ="] {
+            assert!(!debug.contains(secret), "{}", debug);
+        }
+        Ok(())
+    }
+}
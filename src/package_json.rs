@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+
+use crate::npm;
+
+/// Read and parse a package.json file.
+pub fn read(file_path: &std::path::PathBuf) -> Result<serde_json::Value> {
+    let file = std::fs::File::open(file_path)?;
+    let reader = std::io::BufReader::new(file);
+    serde_json::from_reader(reader).context(format!(
+        "Failed to parse package.json: {}",
+        file_path.display()
+    ))
+}
+
+/// Returns true if the version range matches a single exact version.
+///
+/// Example: `1.2.3`, `=1.2.3` and `v1.2.3-beta.1` are exact. `^1.2.3` and `1.x` are not.
+fn is_exact_version(range: &str) -> bool {
+    let version = range.trim();
+    let version = version.strip_prefix('=').unwrap_or(version);
+    let version = version.strip_prefix('v').unwrap_or(version);
+
+    let release = version.split(['-', '+']).next().unwrap_or_default();
+    let components: Vec<_> = release.split('.').collect();
+    components.len() == 3
+        && components
+            .iter()
+            .all(|c| !c.is_empty() && c.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Parse version range.
+///
+/// Ranges which do not pin an exact version are reported as version errors so that
+/// users are told the dependency version is unpinned.
+fn get_parsed_range(range: &str) -> vouch_lib::extension::common::VersionParseResult {
+    if is_exact_version(range) {
+        let version = range.trim().trim_start_matches('=').trim_start_matches('v');
+        return npm::get_parsed_version(&Some(version));
+    }
+    if range.trim().is_empty() {
+        return Err(vouch_lib::extension::common::VersionError::from_missing_version());
+    }
+    Err(vouch_lib::extension::common::VersionError::from_parse_error(range))
+}
+
+/// Parse direct dependencies from a package.json file.
+///
+/// Used when no lockfile is available. Dependency versions are reported as declared
+/// ranges. Dependencies declared using other protocols (`file:`, `git+https:`, ...)
+/// are excluded by protocol.
+pub fn get_dependencies(
+    file_path: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<npm::FileDependencies> {
+    let package_json = read(file_path)?;
+
+    let mut sections = vec!["dependencies", "optionalDependencies", "peerDependencies"];
+    if include_dev_dependencies {
+        sections.push("devDependencies");
+    }
+
+    let mut all_dependencies = npm::FileDependencies::default();
+    for section in sections {
+        let dependencies = match package_json[section].as_object() {
+            Some(v) => v,
+            None => continue,
+        };
+        for (name, range) in dependencies {
+            let range = range.as_str().unwrap_or_default();
+
+            // Aliased dependencies: `"alias": "npm:name@range"`.
+            let (name, range) = match range.strip_prefix("npm:") {
+                Some(aliased_specifier) => match npm::split_specifier(aliased_specifier) {
+                    Some((name, range)) => (name, range),
+                    None => (aliased_specifier, ""),
+                },
+                None => (name.as_str(), range),
+            };
+
+            let protocol = range
+                .split_once(':')
+                .map(|(protocol, _)| protocol.to_string());
+            all_dependencies.insert(
+                protocol,
+                vouch_lib::extension::Dependency {
+                    name: name.to_string(),
+                    version: get_parsed_range(range),
+                },
+            );
+        }
+    }
+    Ok(all_dependencies)
+}
@@ -0,0 +1,140 @@
+use anyhow::{format_err, Result};
+
+/// Maximum package name length accepted by the npm registry.
+static MAX_LENGTH: usize = 214;
+
+/// Returns true if the character may appear unescaped in a package name.
+///
+/// Package names must be URL-safe: they must be unchanged by `encodeURIComponent`.
+fn is_url_safe(character: char) -> bool {
+    character.is_ascii_alphanumeric() || "-._~!*'()".contains(character)
+}
+
+/// Validate a single package name component (scope or name).
+fn validate_component(name: &str, component: &str) -> Result<()> {
+    if component.is_empty() {
+        return Err(format_err!("Invalid package name {}: empty name.", name));
+    }
+    if component.starts_with('.') || component.starts_with('_') {
+        return Err(format_err!(
+            "Invalid package name {}: name can not start with a period or underscore.",
+            name
+        ));
+    }
+    if let Some(character) = component.chars().find(|c| !is_url_safe(*c)) {
+        return Err(format_err!(
+            "Invalid package name {}: name contains URL-unsafe character: {:?}",
+            name,
+            character
+        ));
+    }
+    Ok(())
+}
+
+/// Returns scope and name components. Unscoped package names have no scope.
+///
+/// Example: `@babel/core` yields `(Some("babel"), "core")`.
+fn split(name: &str) -> Result<(Option<&str>, &str)> {
+    match name.strip_prefix('@') {
+        Some(scoped_name) => {
+            let (scope, name_component) = scoped_name.split_once('/').ok_or(format_err!(
+                "Invalid package name {}: scoped names must have the form @scope/name.",
+                name
+            ))?;
+            Ok((Some(scope), name_component))
+        }
+        None => Ok((None, name)),
+    }
+}
+
+/// Validate an npm package name, including scoped names (`@scope/name`).
+pub fn validate(name: &str) -> Result<()> {
+    if name.len() > MAX_LENGTH {
+        return Err(format_err!(
+            "Invalid package name {}: name is longer than {} characters.",
+            name,
+            MAX_LENGTH
+        ));
+    }
+    if name.trim() != name {
+        return Err(format_err!(
+            "Invalid package name {:?}: name can not contain leading or trailing spaces.",
+            name
+        ));
+    }
+
+    let (scope, name_component) = split(name)?;
+    if let Some(scope) = scope {
+        validate_component(name, scope)?;
+    }
+    validate_component(name, name_component)?;
+    Ok(())
+}
+
+/// Returns the package name encoded as a single URL path segment.
+///
+/// The scope separator is percent encoded. Example: `@babel/core` yields `@babel%2fcore`.
+pub fn escape(name: &str) -> String {
+    name.replace('/', "%2f")
+}
+
+/// Returns the package name without its scope.
+///
+/// Example: `@babel/core` yields `core`.
+pub fn unscoped(name: &str) -> &str {
+    match split(name) {
+        Ok((_, name_component)) => name_component,
+        Err(_) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for name in &[
+            "d3",
+            "@babel/core",
+            "lodash.merge",
+            "a-b_c~d!",
+            "@types/node",
+        ] {
+            assert!(validate(name).is_ok(), "{}", name);
+        }
+        let long_name = "a".repeat(MAX_LENGTH + 1);
+        for name in &[
+            "",
+            "@babel",
+            "@babel/",
+            "@/core",
+            ".hidden",
+            "_private",
+            "@_scope/core",
+            "@babel/.core",
+            " core",
+            "core ",
+            "co re",
+            "core/extra",
+            "@babel/core/extra",
+            "ünicode",
+            long_name.as_str(),
+        ] {
+            assert!(validate(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("@babel/core"), "@babel%2fcore");
+        assert_eq!(escape("d3"), "d3");
+    }
+
+    #[test]
+    fn test_unscoped() {
+        assert_eq!(unscoped("@babel/core"), "core");
+        assert_eq!(unscoped("d3"), "d3");
+        assert_eq!(unscoped("@babel"), "@babel");
+    }
+}
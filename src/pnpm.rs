@@ -0,0 +1,583 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::{HashSet, VecDeque};
+
+use crate::{npm, yarn};
+
+/// Returns the lockfile major version number.
+///
+/// Example: `5.4` yields 5, `'6.0'` yields 6.
+fn get_lockfile_major_version(lockfile: &serde_yaml::Value) -> Result<u64> {
+    let version = yarn::yaml_to_string(&lockfile["lockfileVersion"])
+        .ok_or(format_err!("Failed to find lockfileVersion field."))?;
+    let major_version = version.split('.').next().unwrap_or_default();
+    major_version
+        .parse()
+        .context(format!("Failed to parse lockfileVersion: {}", version))
+}
+
+/// Remove peer dependency suffix from a package version reference or key.
+///
+/// Example: `1.0.0_react@17.0.0` (5.x) and `1.0.0(react@17.0.0)` (6.x, 9.x) yield `1.0.0`.
+fn strip_peer_suffix(reference: &str, major_version: u64) -> &str {
+    if major_version >= 6 {
+        return reference.split('(').next().unwrap_or_default();
+    }
+    // Package names may contain underscores, so only the final (version) segment
+    // is searched. Example: `/string_decoder/1.3.0`.
+    let version_index = reference.rfind('/').map(|index| index + 1).unwrap_or(0);
+    match reference[version_index..].find('_') {
+        Some(index) => &reference[..version_index + index],
+        None => reference,
+    }
+}
+
+/// Split a `packages` section key into package name and version.
+///
+/// Key formats by lockfile version:
+/// 5.x: `/@scope/name/1.0.0_peer@2.0.0`
+/// 6.x: `/@scope/name@1.0.0(peer@2.0.0)`
+/// 9.x: `@scope/name@1.0.0(peer@2.0.0)`
+fn parse_package_key(key: &str, major_version: u64) -> Option<(String, String)> {
+    let key = key.strip_prefix('/').unwrap_or(key);
+    if major_version < 6 {
+        let key = strip_peer_suffix(key, major_version);
+        let segments: Vec<_> = key.split('/').collect();
+        if segments.len() < 2 {
+            return None;
+        }
+        let version = segments[segments.len() - 1];
+        let mut name = segments[segments.len() - 2].to_string();
+        if segments.len() >= 3 && segments[segments.len() - 3].starts_with('@') {
+            name = format!("{}/{}", segments[segments.len() - 3], name);
+        }
+        Some((name, version.to_string()))
+    } else {
+        let key = strip_peer_suffix(key, major_version);
+        let (name, version) = npm::split_specifier(key)?;
+        Some((name.to_string(), version.to_string()))
+    }
+}
+
+/// Returns the `packages` (or 9.x `snapshots`) section key for a dependency reference.
+///
+/// Returns None for linked workspace packages.
+fn get_package_key(name: &str, reference: &str, major_version: u64) -> Option<String> {
+    if reference.starts_with("link:") {
+        return None;
+    }
+    match major_version {
+        // Aliased dependencies reference the full package key.
+        5 | 6 if reference.starts_with('/') => Some(reference.to_string()),
+        5 => Some(format!("/{}/{}", name, reference)),
+        6 => Some(format!("/{}@{}", name, reference)),
+        _ => {
+            if strip_peer_suffix(reference, major_version).contains('@') {
+                Some(reference.to_string())
+            } else {
+                Some(format!("{}@{}", name, reference))
+            }
+        }
+    }
+}
+
+/// Returns the version reference of an importer dependency.
+///
+/// 5.x importers map names to references directly. 6.x and 9.x use
+/// `{specifier, version}` maps.
+fn get_importer_reference(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::Mapping(_) => yarn::yaml_to_string(&value["version"]),
+        _ => yarn::yaml_to_string(value),
+    }
+}
+
+/// Returns non-dev root package keys from all importers.
+fn get_root_package_keys(lockfile: &serde_yaml::Value, major_version: u64) -> Vec<String> {
+    // Single project lockfiles (5.x, 6.x) define the root importer at the top level.
+    let mut importers = vec![lockfile];
+    if let Some(lockfile_importers) = lockfile["importers"].as_mapping() {
+        importers.extend(lockfile_importers.iter().map(|(_, importer)| importer));
+    }
+
+    let mut package_keys = Vec::new();
+    for importer in importers {
+        for section in &["dependencies", "optionalDependencies"] {
+            if let Some(dependencies) = importer[*section].as_mapping() {
+                for (name, value) in dependencies {
+                    let name = match yarn::yaml_to_string(name) {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    if let Some(package_key) = get_importer_reference(value)
+                        .and_then(|reference| get_package_key(&name, &reference, major_version))
+                    {
+                        package_keys.push(package_key);
+                    }
+                }
+            }
+        }
+    }
+    package_keys
+}
+
+/// Returns 9.x snapshot keys reachable from the given root keys.
+fn get_reachable_snapshot_keys(
+    snapshots: &serde_yaml::Mapping,
+    root_package_keys: Vec<String>,
+    major_version: u64,
+) -> HashSet<String> {
+    let mut visited = HashSet::new();
+    let mut unprocessed_keys: VecDeque<String> = root_package_keys.into_iter().collect();
+    while let Some(key) = unprocessed_keys.pop_front() {
+        if visited.contains(&key) {
+            continue;
+        }
+        let snapshot = match snapshots.get(&serde_yaml::Value::String(key.clone())) {
+            Some(v) => v,
+            None => continue,
+        };
+        for section in &["dependencies", "optionalDependencies"] {
+            if let Some(dependencies) = snapshot[*section].as_mapping() {
+                for (name, reference) in dependencies {
+                    if let (Some(name), Some(reference)) =
+                        (yarn::yaml_to_string(name), yarn::yaml_to_string(reference))
+                    {
+                        if let Some(package_key) = get_package_key(&name, &reference, major_version)
+                        {
+                            unprocessed_keys.push_back(package_key);
+                        }
+                    }
+                }
+            }
+        }
+        visited.insert(key);
+    }
+    visited
+}
+
+/// Returns the resolution type of a package entry which is not fetched from a registry.
+///
+/// Packages fetched from the npm registry only record an integrity hash in their
+/// resolution. Returns `git`, `directory` or `tarball` for other packages.
+fn get_non_registry_protocol(package_entry: &serde_yaml::Value) -> Option<String> {
+    let resolution = &package_entry["resolution"];
+    if let Some(resolution_type) = resolution["type"].as_str() {
+        Some(resolution_type.to_string())
+    } else if !resolution["tarball"].is_null() {
+        Some("tarball".to_string())
+    } else {
+        None
+    }
+}
+
+/// Parse dependencies from a pnpm-lock.yaml file.
+///
+/// Supports lockfile versions 5.x, 6.x and 9.x. Packages which are not fetched from
+/// the npm registry are excluded by resolution type.
+pub fn get_dependencies(
+    file_path: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<npm::FileDependencies> {
+    let file = std::fs::File::open(file_path)?;
+    let reader = std::io::BufReader::new(file);
+    let lockfile: serde_yaml::Value = serde_yaml::from_reader(reader).context(format!(
+        "Failed to parse pnpm-lock.yaml: {}",
+        file_path.display()
+    ))?;
+    let major_version = get_lockfile_major_version(&lockfile)?;
+
+    let empty_mapping = serde_yaml::Mapping::new();
+    let packages = lockfile["packages"].as_mapping().unwrap_or(&empty_mapping);
+
+    // Package keys paired with their packages section entries.
+    let mut selected_packages = Vec::new();
+    if major_version >= 9 {
+        // 9.x lockfiles do not record dev flags. Instead, dev dependencies are
+        // excluded by walking the snapshots graph from non-dev importer dependencies.
+        let snapshots = lockfile["snapshots"].as_mapping().unwrap_or(&empty_mapping);
+        let snapshot_keys: Vec<String> = if include_dev_dependencies {
+            snapshots
+                .iter()
+                .filter_map(|(key, _)| yarn::yaml_to_string(key))
+                .collect()
+        } else {
+            let root_package_keys = get_root_package_keys(&lockfile, major_version);
+            get_reachable_snapshot_keys(snapshots, root_package_keys, major_version)
+                .into_iter()
+                .collect()
+        };
+        for snapshot_key in snapshot_keys {
+            let package_key = strip_peer_suffix(&snapshot_key, major_version).to_string();
+            let package_entry = packages
+                .get(&serde_yaml::Value::String(package_key.clone()))
+                .cloned()
+                .unwrap_or(serde_yaml::Value::Null);
+            selected_packages.push((package_key, package_entry));
+        }
+    } else {
+        for (key, package_entry) in packages {
+            let key = match yarn::yaml_to_string(key) {
+                Some(v) => v,
+                None => continue,
+            };
+            if !include_dev_dependencies && package_entry["dev"].as_bool() == Some(true) {
+                continue;
+            }
+            selected_packages.push((key, package_entry.clone()));
+        }
+    }
+
+    let mut all_dependencies = npm::FileDependencies::default();
+    for (package_key, package_entry) in selected_packages {
+        let (key_name, key_version) = match parse_package_key(&package_key, major_version) {
+            Some(v) => v,
+            None => continue,
+        };
+        // Non-registry packages record their name and version explicitly.
+        let name = package_entry["name"]
+            .as_str()
+            .map(|v| v.to_string())
+            .unwrap_or(key_name);
+        let version = yarn::yaml_to_string(&package_entry["version"]).unwrap_or(key_version);
+
+        all_dependencies.insert(
+            get_non_registry_protocol(&package_entry),
+            vouch_lib::extension::Dependency {
+                name,
+                version: npm::get_parsed_version(&Some(version.as_str())),
+            },
+        );
+    }
+    Ok(all_dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, get_names_versions};
+
+    static LOCKFILE_5: &str = r#"lockfileVersion: 5.4
+
+specifiers:
+  '@babel/core': ^7.0.0
+  jest: ^27.0.0
+  my-lib: github:owner/my-lib
+  react-dom: ^17.0.0
+  string-width-cjs: npm:string-width@^4.2.3
+
+dependencies:
+  '@babel/core': 7.12.3
+  my-lib: github.com/owner/my-lib/0123abc
+  react-dom: 17.0.2_react@17.0.2
+  string-width-cjs: /string-width/4.2.3
+
+devDependencies:
+  jest: 27.4.5
+
+packages:
+
+  /@babel/core/7.12.3:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /react-dom/17.0.2_react@17.0.2:
+    resolution: {integrity: sha512-AAAA}
+    peerDependencies:
+      react: 17.0.2
+    dependencies:
+      react: 17.0.2
+      string_decoder: 1.3.0
+    dev: false
+
+  /react/17.0.2:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /string_decoder/1.3.0:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /string-width/4.2.3:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /jest/27.4.5:
+    resolution: {integrity: sha512-AAAA}
+    dev: true
+
+  github.com/owner/my-lib/0123abc:
+    resolution: {tarball: https://codeload.github.com/owner/my-lib/tar.gz/0123abc}
+    name: my-lib
+    version: 1.0.0
+    dev: false
+"#;
+
+    static LOCKFILE_6: &str = r#"lockfileVersion: '6.0'
+
+dependencies:
+  '@babel/core':
+    specifier: ^7.0.0
+    version: 7.12.3
+  react-dom:
+    specifier: ^17.0.0
+    version: 17.0.2(react@17.0.2)
+  string-width-cjs:
+    specifier: npm:string-width@^4.2.3
+    version: /string-width@4.2.3
+
+devDependencies:
+  jest:
+    specifier: ^27.0.0
+    version: 27.4.5
+
+packages:
+
+  /@babel/core@7.12.3:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /react-dom@17.0.2(react@17.0.2):
+    resolution: {integrity: sha512-AAAA}
+    peerDependencies:
+      react: 17.0.2
+    dependencies:
+      react: 17.0.2
+      string_decoder: 1.3.0
+    dev: false
+
+  /react@17.0.2:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /string_decoder@1.3.0:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /string-width@4.2.3:
+    resolution: {integrity: sha512-AAAA}
+    dev: false
+
+  /jest@27.4.5:
+    resolution: {integrity: sha512-AAAA}
+    dev: true
+"#;
+
+    static LOCKFILE_9: &str = r#"lockfileVersion: '9.0'
+
+importers:
+
+  .:
+    dependencies:
+      '@babel/core':
+        specifier: ^7.0.0
+        version: 7.12.3
+      react-dom:
+        specifier: ^17.0.0
+        version: 17.0.2(react@17.0.2)
+      string-width-cjs:
+        specifier: npm:string-width@^4.2.3
+        version: string-width@4.2.3
+    devDependencies:
+      jest:
+        specifier: ^27.0.0
+        version: 27.4.5
+
+packages:
+
+  '@babel/core@7.12.3':
+    resolution: {integrity: sha512-AAAA}
+
+  jest-util@27.4.2:
+    resolution: {integrity: sha512-AAAA}
+
+  jest@27.4.5:
+    resolution: {integrity: sha512-AAAA}
+
+  react-dom@17.0.2:
+    resolution: {integrity: sha512-AAAA}
+    peerDependencies:
+      react: 17.0.2
+
+  react@17.0.2:
+    resolution: {integrity: sha512-AAAA}
+
+  string-width@4.2.3:
+    resolution: {integrity: sha512-AAAA}
+
+  string_decoder@1.3.0:
+    resolution: {integrity: sha512-AAAA}
+
+snapshots:
+
+  '@babel/core@7.12.3': {}
+
+  jest-util@27.4.2: {}
+
+  jest@27.4.5:
+    dependencies:
+      jest-util: 27.4.2
+
+  react-dom@17.0.2(react@17.0.2):
+    dependencies:
+      react: 17.0.2
+      string_decoder: 1.3.0
+
+  react@17.0.2: {}
+
+  string-width@4.2.3: {}
+
+  string_decoder@1.3.0: {}
+"#;
+
+    fn expected_registry_dependencies() -> Vec<&'static str> {
+        vec![
+            "@babel/core@7.12.3",
+            "react@17.0.2",
+            "react-dom@17.0.2",
+            "string-width@4.2.3",
+            "string_decoder@1.3.0",
+        ]
+    }
+
+    #[test]
+    fn test_parse_package_key() {
+        let cases = vec![
+            ("/string_decoder/1.3.0", 5, ("string_decoder", "1.3.0")),
+            ("/@babel/core/7.12.3", 5, ("@babel/core", "7.12.3")),
+            ("/react-dom/17.0.2_react@17.0.2", 5, ("react-dom", "17.0.2")),
+            (
+                "/@scope/some_name/1.0.0_@babel+core@7.12.3",
+                5,
+                ("@scope/some_name", "1.0.0"),
+            ),
+            ("/string_decoder@1.3.0", 6, ("string_decoder", "1.3.0")),
+            ("/@babel/core@7.12.3", 6, ("@babel/core", "7.12.3")),
+            (
+                "/react-dom@17.0.2(react@17.0.2)",
+                6,
+                ("react-dom", "17.0.2"),
+            ),
+            ("string_decoder@1.3.0", 9, ("string_decoder", "1.3.0")),
+            (
+                "@scope/name@1.0.0(@babel/core@7.12.3)(react@17.0.2)",
+                9,
+                ("@scope/name", "1.0.0"),
+            ),
+        ];
+        for (key, major_version, (name, version)) in cases {
+            assert_eq!(
+                parse_package_key(key, major_version),
+                Some((name.to_string(), version.to_string())),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_package_key() {
+        let cases = vec![
+            (
+                "react-dom",
+                "17.0.2_react@17.0.2",
+                5,
+                Some("/react-dom/17.0.2_react@17.0.2"),
+            ),
+            (
+                "string-width-cjs",
+                "/string-width/4.2.3",
+                5,
+                Some("/string-width/4.2.3"),
+            ),
+            ("@babel/core", "7.12.3", 6, Some("/@babel/core@7.12.3")),
+            (
+                "string-width-cjs",
+                "/string-width@4.2.3",
+                6,
+                Some("/string-width@4.2.3"),
+            ),
+            (
+                "react-dom",
+                "17.0.2(react@17.0.2)",
+                9,
+                Some("react-dom@17.0.2(react@17.0.2)"),
+            ),
+            (
+                "string-width-cjs",
+                "string-width@4.2.3",
+                9,
+                Some("string-width@4.2.3"),
+            ),
+            ("my-workspace", "link:packages/my-workspace", 9, None),
+        ];
+        for (name, reference, major_version, expected_key) in cases {
+            assert_eq!(
+                get_package_key(name, reference, major_version).as_deref(),
+                expected_key,
+                "{}: {}",
+                name,
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_dependencies_5() -> Result<()> {
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_5, None)?;
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            expected_registry_dependencies()
+        );
+        assert_eq!(
+            get_names_versions(&dependencies.excluded_dependencies["tarball"]),
+            vec!["my-lib@1.0.0"]
+        );
+
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_5, None)?;
+        let dependencies = get_dependencies(&file_path, true)?;
+        assert!(get_names_versions(&dependencies.registry_dependencies)
+            .contains(&"jest@27.4.5".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_dependencies_6() -> Result<()> {
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_6, None)?;
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            expected_registry_dependencies()
+        );
+        assert!(dependencies.excluded_dependencies.is_empty());
+
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_6, None)?;
+        let dependencies = get_dependencies(&file_path, true)?;
+        assert!(get_names_versions(&dependencies.registry_dependencies)
+            .contains(&"jest@27.4.5".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_dependencies_9() -> Result<()> {
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_9, None)?;
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            expected_registry_dependencies()
+        );
+
+        let (_project_directory, file_path) =
+            testing::write_lockfile("pnpm-lock.yaml", LOCKFILE_9, None)?;
+        let dependencies = get_dependencies(&file_path, true)?;
+        let names_versions = get_names_versions(&dependencies.registry_dependencies);
+        assert!(names_versions.contains(&"jest@27.4.5".to_string()));
+        assert!(names_versions.contains(&"jest-util@27.4.2".to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::{http, npmrc, version_range};
+
+/// Returns true if the specifier is a registry version, range or dist-tag.
+///
+/// Git, file, link and URL specifiers can not be resolved against the registry.
+fn is_registry_specifier(specifier: &str) -> bool {
+    !(specifier.contains(':') || specifier.contains('/'))
+}
+
+/// Returns true if the given version is marked as deprecated in the packument.
+fn is_deprecated(packument: &serde_json::Value, version: &str) -> bool {
+    packument["versions"][version]["deprecated"]
+        .as_str()
+        .map(|v| !v.is_empty())
+        .unwrap_or_default()
+}
+
+/// Returns the highest version which is neither a prerelease nor deprecated.
+pub fn get_highest_stable_version(packument: &serde_json::Value) -> Option<String> {
+    packument["versions"]
+        .as_object()?
+        .keys()
+        .filter_map(|v| version_range::parse_version(v).map(|parsed| (parsed, v)))
+        .filter(|(parsed, v)| parsed.pre.is_empty() && !is_deprecated(packument, v))
+        .max()
+        .map(|(_, v)| v.clone())
+}
+
+/// Select a package version from a packument given a version, range or dist-tag.
+///
+/// Follows npm's selection rules: dist-tags are matched first. For ranges, the
+/// `latest` dist-tag is preferred if it satisfies the range. Otherwise, the highest
+/// satisfying version is chosen, preferring versions which are not deprecated.
+pub fn pick_version(packument: &serde_json::Value, specifier: &str) -> Result<String> {
+    let specifier = specifier.trim();
+    let specifier = if specifier.is_empty() {
+        "latest"
+    } else {
+        specifier
+    };
+
+    if let Some(version) = packument["dist-tags"][specifier].as_str() {
+        return Ok(version.to_string());
+    }
+    if specifier == "latest" {
+        return get_highest_stable_version(packument)
+            .ok_or(format_err!("Failed to find a stable package version."));
+    }
+
+    let versions = packument["versions"]
+        .as_object()
+        .ok_or(format_err!("Failed to find versions JSON section."))?;
+    if versions.contains_key(specifier) {
+        return Ok(specifier.to_string());
+    }
+
+    let range = version_range::Range::parse(specifier)?;
+    if let Some(latest_version) = packument["dist-tags"]["latest"].as_str() {
+        if let Some(version) = version_range::parse_version(latest_version) {
+            if range.matches(&version) {
+                return Ok(latest_version.to_string());
+            }
+        }
+    }
+
+    let mut candidates: Vec<_> = versions
+        .keys()
+        .filter_map(|v| version_range::parse_version(v).map(|parsed| (parsed, v)))
+        .filter(|(parsed, _)| range.matches(parsed))
+        .map(|(parsed, v)| (!is_deprecated(packument, v), parsed, v))
+        .collect();
+    candidates.sort();
+    let (_, _, version) = candidates.last().ok_or(format_err!(
+        "No version of package {} satisfies: {}",
+        packument["name"].as_str().unwrap_or_default(),
+        specifier
+    ))?;
+    Ok(version.to_string())
+}
+
+/// Dependency edge between a dependent package and a required package.
+#[derive(Debug, Clone)]
+struct Edge {
+    name: String,
+    specifier: String,
+    is_optional: bool,
+}
+
+/// Returns the dependency edges of a package version manifest.
+///
+/// Includes dependencies, optional dependencies and non-optional peer dependencies,
+/// which npm installs by default.
+fn get_edges(manifest: &serde_json::Value) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    for (section, is_optional) in &[
+        ("dependencies", false),
+        ("optionalDependencies", true),
+        ("peerDependencies", false),
+    ] {
+        let dependencies = match manifest[*section].as_object() {
+            Some(v) => v,
+            None => continue,
+        };
+        for (name, specifier) in dependencies {
+            if *section == "peerDependencies"
+                && manifest["peerDependenciesMeta"][name]["optional"]
+                    .as_bool()
+                    .unwrap_or_default()
+            {
+                continue;
+            }
+            edges.push(Edge {
+                name: name.clone(),
+                specifier: specifier.as_str().unwrap_or_default().to_string(),
+                is_optional: *is_optional,
+            });
+        }
+    }
+    edges
+}
+
+/// Resolves package dependency trees using registry packuments.
+///
+/// Dependencies are resolved breadth first. A resolved version is reused wherever it
+/// satisfies a later dependency specifier, approximating npm's deduplication.
+struct Resolver<'a> {
+    npm_config: &'a npmrc::Config,
+    http_client: &'a http::Client,
+    packuments: HashMap<String, serde_json::Value>,
+    resolved_versions: HashMap<String, Vec<String>>,
+    dependencies: BTreeSet<vouch_lib::extension::Dependency>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(npm_config: &'a npmrc::Config, http_client: &'a http::Client) -> Self {
+        Self {
+            npm_config,
+            http_client,
+            packuments: HashMap::new(),
+            resolved_versions: HashMap::new(),
+            dependencies: BTreeSet::new(),
+        }
+    }
+
+    fn get_packument(&mut self, package_name: &str) -> Result<&serde_json::Value> {
+        if !self.packuments.contains_key(package_name) {
+            let registry_url = self.npm_config.get_registry_url(package_name)?;
+            let packument = crate::get_registry_entry_json(
+                self.npm_config,
+                self.http_client,
+                &registry_url,
+                package_name,
+                crate::PackumentFormat::Abbreviated,
+            )?;
+            self.packuments.insert(package_name.to_string(), packument);
+        }
+        Ok(&self.packuments[package_name])
+    }
+
+    /// Returns a previously resolved version which satisfies the specifier.
+    fn get_resolved_version(&self, package_name: &str, specifier: &str) -> Option<String> {
+        let range = version_range::Range::parse(specifier).ok()?;
+        self.resolved_versions
+            .get(package_name)?
+            .iter()
+            .find(|v| {
+                version_range::parse_version(v)
+                    .map(|v| range.matches(&v))
+                    .unwrap_or_default()
+            })
+            .cloned()
+    }
+
+    /// Resolve an edge. Returns the resolved version if it has not been visited before.
+    fn resolve_edge(&mut self, edge: &Edge) -> Result<Option<(String, String)>> {
+        // Aliased dependencies: `"alias": "npm:name@range"`.
+        let (package_name, specifier) = match edge.specifier.strip_prefix("npm:") {
+            Some(aliased_specifier) => match crate::npm::split_specifier(aliased_specifier) {
+                Some((name, range)) => (name.to_string(), range.to_string()),
+                None => (aliased_specifier.to_string(), String::new()),
+            },
+            None => (edge.name.clone(), edge.specifier.clone()),
+        };
+
+        if !is_registry_specifier(&specifier) {
+            self.dependencies.insert(vouch_lib::extension::Dependency {
+                name: package_name,
+                version: Err(
+                    vouch_lib::extension::common::VersionError::from_parse_error(&specifier),
+                ),
+            });
+            return Ok(None);
+        }
+        if self
+            .get_resolved_version(&package_name, &specifier)
+            .is_some()
+        {
+            return Ok(None);
+        }
+
+        let version = self
+            .get_packument(&package_name)
+            .and_then(|packument| pick_version(packument, &specifier))
+            .context(format!(
+                "Failed to resolve dependency: {}@{}",
+                package_name, specifier
+            ))?;
+        let versions = self
+            .resolved_versions
+            .entry(package_name.clone())
+            .or_default();
+        if versions.contains(&version) {
+            return Ok(None);
+        }
+        versions.push(version.clone());
+        Ok(Some((package_name, version)))
+    }
+
+    fn resolve(&mut self, package_name: &str, specifier: &str) -> Result<String> {
+        let root_edge = Edge {
+            name: package_name.to_string(),
+            specifier: specifier.to_string(),
+            is_optional: false,
+        };
+        let (_, root_version) = self
+            .resolve_edge(&root_edge)?
+            .ok_or(format_err!("Failed to resolve package: {}", package_name))?;
+
+        let mut unprocessed = VecDeque::new();
+        unprocessed.push_back((package_name.to_string(), root_version.clone()));
+        while let Some((name, version)) = unprocessed.pop_front() {
+            let manifest = self.get_packument(&name)?["versions"][&version].clone();
+            for edge in get_edges(&manifest) {
+                let resolved = match self.resolve_edge(&edge) {
+                    Ok(v) => v,
+                    // Optional dependencies which fail to resolve are skipped by npm.
+                    Err(_) if edge.is_optional => continue,
+                    Err(error) => return Err(error),
+                };
+                if let Some((dependency_name, dependency_version)) = resolved {
+                    self.dependencies.insert(vouch_lib::extension::Dependency {
+                        name: dependency_name.clone(),
+                        version: Ok(dependency_version.clone()),
+                    });
+                    unprocessed.push_back((dependency_name, dependency_version));
+                }
+            }
+        }
+        Ok(root_version)
+    }
+}
+
+/// Resolve the dependency tree of a registry package without invoking npm.
+///
+/// Returns the resolved package version and its transitive dependencies.
+pub fn resolve_dependencies(
+    npm_config: &npmrc::Config,
+    http_client: &http::Client,
+    package_name: &str,
+    package_version: &Option<&str>,
+) -> Result<(String, Vec<vouch_lib::extension::Dependency>)> {
+    let mut resolver = Resolver::new(npm_config, http_client);
+    let version = resolver.resolve(package_name, package_version.unwrap_or_default())?;
+    Ok((version, resolver.dependencies.into_iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_packument() -> serde_json::Value {
+        serde_json::json!({
+            "name": "example",
+            "dist-tags": {"latest": "1.2.0", "next": "2.0.0-beta.1"},
+            "versions": {
+                "1.0.0": {},
+                "1.1.0": {},
+                "1.2.0": {},
+                "1.3.0": {"deprecated": "Use 1.2.0."},
+                "1.4.0-beta.1": {},
+                "2.0.0-beta.1": {},
+                "2.1.0": {"deprecated": "Broken release."},
+                "2.2.0": {"deprecated": "Broken release."},
+            }
+        })
+    }
+
+    #[test]
+    fn test_pick_version() {
+        let packument = get_packument();
+        let cases = vec![
+            // Dist-tags.
+            ("", "1.2.0"),
+            ("latest", "1.2.0"),
+            ("next", "2.0.0-beta.1"),
+            // Exact versions, including deprecated versions.
+            ("1.1.0", "1.1.0"),
+            ("1.3.0", "1.3.0"),
+            // The latest dist-tag is preferred when it satisfies the range.
+            ("^1.0.0", "1.2.0"),
+            ("*", "1.2.0"),
+            // Otherwise the highest non-deprecated satisfying version is chosen.
+            ("~1.1.0", "1.1.0"),
+            (">=1.3.0 <2", "1.3.0"),
+            ("<1.2.0", "1.1.0"),
+            // Deprecated versions are chosen if no other version satisfies the range.
+            ("^2.0.0", "2.2.0"),
+            // Prereleases are only chosen if the range includes them.
+            ("^1.4.0-beta.1", "1.4.0-beta.1"),
+        ];
+        for (specifier, expected_version) in cases {
+            assert_eq!(
+                pick_version(&packument, specifier).unwrap(),
+                expected_version,
+                "{}",
+                specifier
+            );
+        }
+    }
+
+    #[test]
+    fn test_pick_version_latest_without_dist_tag() {
+        let mut packument = get_packument();
+        packument["dist-tags"] = serde_json::json!({});
+        assert_eq!(pick_version(&packument, "latest").unwrap(), "1.2.0");
+        assert_eq!(pick_version(&packument, "^1.0.0").unwrap(), "1.2.0");
+    }
+
+    #[test]
+    fn test_pick_version_unsatisfied() {
+        let packument = get_packument();
+        assert!(pick_version(&packument, "^3.0.0").is_err());
+        assert!(pick_version(&packument, "no-such-tag").is_err());
+    }
+
+    #[test]
+    fn test_is_registry_specifier() {
+        for specifier in &["^1.0.0", "1.x", "latest", ">=1 <2 || 3"] {
+            assert!(is_registry_specifier(specifier), "{}", specifier);
+        }
+        for specifier in &[
+            "github:owner/repo",
+            "owner/repo",
+            "file:../local",
+            "link:../local",
+            "https://example.com/package.tgz",
+            "git+ssh://git@github.com/owner/repo.git",
+        ] {
+            assert!(!is_registry_specifier(specifier), "{}", specifier);
+        }
+    }
+
+    #[test]
+    fn test_resolve_edge_alias_and_non_registry() -> Result<()> {
+        let npm_config = npmrc::Config::default();
+        let http_client = http::Client::new(&npm_config)?;
+        let mut resolver = Resolver::new(&npm_config, &http_client);
+        resolver
+            .packuments
+            .insert("example".to_string(), get_packument());
+
+        let alias_edge = Edge {
+            name: "example-alias".to_string(),
+            specifier: "npm:example@~1.1.0".to_string(),
+            is_optional: false,
+        };
+        assert_eq!(
+            resolver.resolve_edge(&alias_edge)?,
+            Some(("example".to_string(), "1.1.0".to_string()))
+        );
+        // Versions which have already been resolved are not visited again.
+        assert_eq!(resolver.resolve_edge(&alias_edge)?, None);
+
+        let git_edge = Edge {
+            name: "other".to_string(),
+            specifier: "github:owner/other".to_string(),
+            is_optional: false,
+        };
+        assert_eq!(resolver.resolve_edge(&git_edge)?, None);
+        assert_eq!(
+            resolver.dependencies.into_iter().collect::<Vec<_>>(),
+            vec![vouch_lib::extension::Dependency {
+                name: "other".to_string(),
+                version: Err(
+                    vouch_lib::extension::common::VersionError::from_parse_error(
+                        "github:owner/other"
+                    )
+                ),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_edges() {
+        let manifest = serde_json::json!({
+            "dependencies": {"a": "^1.0.0"},
+            "optionalDependencies": {"b": "^2.0.0"},
+            "peerDependencies": {"c": "^3.0.0", "d": "^4.0.0"},
+            "peerDependenciesMeta": {"d": {"optional": true}},
+            "devDependencies": {"e": "^5.0.0"}
+        });
+        let edges: Vec<_> = get_edges(&manifest)
+            .into_iter()
+            .map(|edge| (edge.name, edge.is_optional))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("a".to_string(), false),
+                ("b".to_string(), true),
+                ("c".to_string(), false)
+            ]
+        );
+    }
+}
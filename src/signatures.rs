@@ -0,0 +1,370 @@
+use anyhow::{format_err, Context, Result};
+use p256::ecdsa::signature::Verifier;
+use p256::pkcs8::DecodePublicKey;
+
+/// Key type and signing scheme of supported registry keys.
+static ECDSA_P256_SHA256: &str = "ecdsa-sha2-nistp256";
+
+/// Public key which a registry uses to sign package versions.
+///
+/// Published at `<registry>/-/npm/v1/keys`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RegistryKey {
+    pub keyid: String,
+    pub keytype: String,
+    pub scheme: String,
+
+    /// Base64 encoded DER SubjectPublicKeyInfo.
+    pub key: String,
+
+    /// ISO 8601 time after which the key must not be used, if any.
+    pub expires: Option<String>,
+}
+
+/// Parse the registry keys endpoint response.
+pub fn parse_keys(body: &[u8]) -> Result<Vec<RegistryKey>> {
+    #[derive(serde::Deserialize)]
+    struct Keys {
+        keys: Vec<RegistryKey>,
+    }
+    let keys: Keys = serde_json::from_slice(body).context("Failed to parse registry keys.")?;
+    Ok(keys.keys)
+}
+
+/// Registry signature verification result for a package version.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureStatus {
+    /// A registry signature was verified with the given registry key.
+    Verified { keyid: String },
+    /// The registry publishes signing keys, but the package version is not signed.
+    Missing,
+    /// The registry does not publish signing keys.
+    Unsupported,
+    /// The registry keys are not available offline.
+    Unavailable,
+}
+
+impl std::fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verified { keyid } => write!(f, "verified (key {})", keyid),
+            Self::Missing => write!(f, "missing"),
+            Self::Unsupported => write!(f, "not supported by registry"),
+            Self::Unavailable => write!(f, "registry keys not available offline"),
+        }
+    }
+}
+
+/// Returns true if a key used by a signature expired before the package version was published.
+///
+/// Returns `None` if the key expires and the publish time is unknown.
+pub fn is_expired(key: &RegistryKey, publish_time: Option<&str>) -> Option<bool> {
+    match &key.expires {
+        Some(expires) => Some(normalize_time(publish_time?) > normalize_time(expires)),
+        None => Some(false),
+    }
+}
+
+/// Returns true if a signature of the package version uses an expiring registry key, so
+/// that its publish time is needed to check the key expiry.
+pub fn requires_publish_time(version_json: &serde_json::Value, keys: &[RegistryKey]) -> bool {
+    version_json["dist"]["signatures"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|signature| signature["keyid"].as_str())
+        .any(|keyid| {
+            keys.iter()
+                .any(|key| key.keyid == keyid && key.expires.is_some())
+        })
+}
+
+/// Normalize an ISO 8601 UTC time so that times compare in chronological order.
+///
+/// npm records times with millisecond precision, which may be omitted.
+fn normalize_time(time: &str) -> String {
+    let time = time.trim_end_matches('Z');
+    match time.split_once('.') {
+        Some((seconds, fraction)) => format!("{}.{:0<3}", seconds, fraction),
+        None => format!("{}.000", time),
+    }
+}
+
+/// Verify registry signatures (`dist.signatures`) of a package version.
+///
+/// Signatures are ECDSA P-256 SHA-256 signatures over `<name>@<version>:<integrity>`.
+/// Returns the verification status if a signature is valid, or if the version is not
+/// signed. Signatures which are invalid, or which use unknown or expired keys, are errors.
+pub fn verify(
+    package_name: &str,
+    package_version: &str,
+    version_json: &serde_json::Value,
+    keys: &[RegistryKey],
+    publish_time: Option<&str>,
+) -> Result<SignatureStatus> {
+    let signatures = match version_json["dist"]["signatures"].as_array() {
+        Some(v) if !v.is_empty() => v,
+        _ => return Ok(SignatureStatus::Missing),
+    };
+    let integrity = version_json["dist"]["integrity"]
+        .as_str()
+        .ok_or(format_err!(
+            "Signed package version has no integrity: {}@{}",
+            package_name,
+            package_version
+        ))?;
+    let message = format!("{}@{}:{}", package_name, package_version, integrity);
+
+    let mut errors = Vec::new();
+    for signature in signatures {
+        let keyid = signature["keyid"].as_str().unwrap_or_default();
+        let key = match keys.iter().find(|key| key.keyid == keyid) {
+            Some(v) => v,
+            None => {
+                errors.push(format!("unknown registry key: {}", keyid));
+                continue;
+            }
+        };
+        match is_expired(key, publish_time) {
+            Some(false) => {}
+            Some(true) => {
+                errors.push(format!(
+                    "registry key expired before publication: {}",
+                    keyid
+                ));
+                continue;
+            }
+            None => {
+                errors.push(format!(
+                    "publish time unknown for expiring registry key: {}",
+                    keyid
+                ));
+                continue;
+            }
+        }
+        match verify_signature(key, signature["sig"].as_str().unwrap_or_default(), &message) {
+            Ok(()) => {
+                return Ok(SignatureStatus::Verified {
+                    keyid: keyid.to_string(),
+                })
+            }
+            Err(error) => errors.push(format!("{}: {:#}", keyid, error)),
+        }
+    }
+    Err(format_err!(
+        "Registry signature verification failed for {}@{}:\n{}",
+        package_name,
+        package_version,
+        errors.join("\n")
+    ))
+}
+
+fn verify_signature(key: &RegistryKey, signature: &str, message: &str) -> Result<()> {
+    if key.keytype != ECDSA_P256_SHA256 || key.scheme != ECDSA_P256_SHA256 {
+        return Err(format_err!(
+            "Unsupported registry key type: {} ({})",
+            key.keytype,
+            key.scheme
+        ));
+    }
+    let key_der = base64::decode(&key.key).context("Invalid registry key encoding.")?;
+    let verifying_key = p256::ecdsa::VerifyingKey::from_public_key_der(&key_der)
+        .map_err(|_| format_err!("Invalid registry key."))?;
+    verify_ecdsa_signature(&verifying_key, signature, message.as_bytes())
+}
+
+/// Verify a base64 encoded DER ECDSA P-256 SHA-256 signature.
+fn verify_ecdsa_signature(
+    verifying_key: &p256::ecdsa::VerifyingKey,
+    signature: &str,
+    message: &[u8],
+) -> Result<()> {
+    let signature = base64::decode(signature).context("Invalid signature encoding.")?;
+    let signature = p256::ecdsa::Signature::from_der(&signature)
+        .map_err(|_| format_err!("Invalid signature."))?;
+    verifying_key
+        .verify(message, &signature)
+        .map_err(|_| format_err!("Signature does not match."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::pkcs8::EncodePublicKey;
+
+    static INTEGRITY: &str = "sha512-SnbZ2uAQWMGeyCkgJcYhSDQBRlnC4MqjzhRI3vrqm69eIrqWRpWX1W0uRUlA1gxTTvCJCpT6Ztpea34axB0ZCA==";
+
+    /// Returns a deterministic signing key.
+    fn get_signing_key(seed: u8) -> p256::ecdsa::SigningKey {
+        p256::ecdsa::SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+    }
+
+    fn get_registry_key(
+        keyid: &str,
+        signing_key: &p256::ecdsa::SigningKey,
+        expires: Option<&str>,
+    ) -> RegistryKey {
+        let key_der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .unwrap()
+            .into_vec();
+        RegistryKey {
+            keyid: keyid.to_string(),
+            keytype: ECDSA_P256_SHA256.to_string(),
+            scheme: ECDSA_P256_SHA256.to_string(),
+            key: base64::encode(key_der),
+            expires: expires.map(|v| v.to_string()),
+        }
+    }
+
+    /// Returns version metadata signed over `<name>@<version>:<integrity>`.
+    fn get_signed_version_json(
+        keyid: &str,
+        signing_key: &p256::ecdsa::SigningKey,
+        message: &str,
+    ) -> serde_json::Value {
+        let signature: p256::ecdsa::Signature = signing_key.sign(message.as_bytes());
+        serde_json::json!({
+            "dist": {
+                "integrity": INTEGRITY,
+                "signatures": [{
+                    "keyid": keyid,
+                    "sig": base64::encode(signature.to_der().as_bytes())
+                }]
+            }
+        })
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let signing_key = get_signing_key(1);
+        let keys = vec![
+            get_registry_key("SHA256:current", &signing_key, None),
+            get_registry_key(
+                "SHA256:expiring",
+                &signing_key,
+                Some("2023-01-29T00:00:00.000Z"),
+            ),
+        ];
+        let message = format!("d3@6.5.0:{}", INTEGRITY);
+
+        // Valid signature.
+        let version_json = get_signed_version_json("SHA256:current", &signing_key, &message);
+        assert_eq!(
+            verify("d3", "6.5.0", &version_json, &keys, None)?,
+            SignatureStatus::Verified {
+                keyid: "SHA256:current".to_string()
+            }
+        );
+
+        // Signature over a different package version, or by a different key.
+        let tampered_version_json = get_signed_version_json(
+            "SHA256:current",
+            &signing_key,
+            &format!("d3@6.4.0:{}", INTEGRITY),
+        );
+        assert!(verify("d3", "6.5.0", &tampered_version_json, &keys, None).is_err());
+        let forged_version_json =
+            get_signed_version_json("SHA256:current", &get_signing_key(2), &message);
+        assert!(verify("d3", "6.5.0", &forged_version_json, &keys, None).is_err());
+
+        // Tampered integrity.
+        let mut tampered_version_json = version_json.clone();
+        tampered_version_json["dist"]["integrity"] = serde_json::json!("sha512-AAAA");
+        assert!(verify("d3", "6.5.0", &tampered_version_json, &keys, None).is_err());
+
+        // Unknown key.
+        let version_json = get_signed_version_json("SHA256:unknown", &signing_key, &message);
+        let error = verify("d3", "6.5.0", &version_json, &keys, None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unknown registry key: SHA256:unknown"));
+
+        // Expiring key, used before and after expiry, or with an unknown publish time.
+        let version_json = get_signed_version_json("SHA256:expiring", &signing_key, &message);
+        assert_eq!(
+            verify(
+                "d3",
+                "6.5.0",
+                &version_json,
+                &keys,
+                Some("2023-01-28T12:00:00Z")
+            )?,
+            SignatureStatus::Verified {
+                keyid: "SHA256:expiring".to_string()
+            }
+        );
+        let error = verify(
+            "d3",
+            "6.5.0",
+            &version_json,
+            &keys,
+            Some("2023-01-29T00:00:00.001Z"),
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("registry key expired before publication"));
+        assert!(verify("d3", "6.5.0", &version_json, &keys, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_unsigned() -> Result<()> {
+        let keys = vec![get_registry_key(
+            "SHA256:current",
+            &get_signing_key(1),
+            None,
+        )];
+        let version_json = serde_json::json!({"dist": {"integrity": INTEGRITY}});
+        assert_eq!(
+            verify("d3", "6.5.0", &version_json, &keys, None)?,
+            SignatureStatus::Missing
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let key = get_registry_key(
+            "SHA256:expiring",
+            &get_signing_key(1),
+            Some("2023-01-29T00:00:00.000Z"),
+        );
+        assert_eq!(is_expired(&key, Some("2023-01-29T00:00:00Z")), Some(false));
+        assert_eq!(is_expired(&key, Some("2023-01-29T00:00:00.5Z")), Some(true));
+        assert_eq!(is_expired(&key, None), None);
+    }
+
+    #[test]
+    fn test_requires_publish_time() {
+        let get_key = |keyid: &str, expires: Option<&str>| RegistryKey {
+            keyid: keyid.to_string(),
+            keytype: ECDSA_P256_SHA256.to_string(),
+            scheme: ECDSA_P256_SHA256.to_string(),
+            key: String::new(),
+            expires: expires.map(|v| v.to_string()),
+        };
+        let keys = vec![
+            get_key("SHA256:current", None),
+            get_key("SHA256:expiring", Some("2025-01-29T00:00:00.000Z")),
+        ];
+        let get_version_json = |keyid: &str| {
+            serde_json::json!({
+                "dist": {"signatures": [{"keyid": keyid, "sig": ""}]}
+            })
+        };
+
+        assert!(!requires_publish_time(
+            &get_version_json("SHA256:current"),
+            &keys
+        ));
+        assert!(requires_publish_time(
+            &get_version_json("SHA256:expiring"),
+            &keys
+        ));
+        assert!(!requires_publish_time(&serde_json::json!({}), &keys));
+    }
+}
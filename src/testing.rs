@@ -0,0 +1,190 @@
+use std::io::{Read, Write};
+
+/// Response headers given as (name, value) pairs.
+pub type Headers<'a> = Vec<(&'a str, &'a str)>;
+
+/// Request received by a test server.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+
+    /// Header values keyed by lowercase header name.
+    pub headers: std::collections::HashMap<String, String>,
+}
+
+/// Minimal HTTP server which serves fixed responses by request path.
+///
+/// Unknown paths receive a `404 Not Found` response. Requests are recorded.
+pub struct Server {
+    url: url::Url,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<Request>>>,
+}
+
+impl Server {
+    /// Start a server given (path, status, body) responses.
+    ///
+    /// The server is bound to 127.0.0.1. The host name of its URL is given, so that
+    /// servers can be told apart by host name.
+    pub fn new(host_name: &str, responses: Vec<(&str, u16, Vec<u8>)>) -> Self {
+        Self::with_headers(
+            host_name,
+            responses
+                .into_iter()
+                .map(|(path, status, body)| (path, status, vec![], body))
+                .collect(),
+        )
+    }
+
+    /// Start a server given (path, status, headers, body) responses.
+    ///
+    /// If a path is given more than once, its responses are served in order and the
+    /// last response is repeated.
+    pub fn with_headers(
+        host_name: &str,
+        responses: Vec<(&str, u16, Headers<'_>, Vec<u8>)>,
+    ) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let url = url::Url::parse(&format!(
+            "http://{}:{}/",
+            host_name,
+            listener.local_addr().expect("test server address").port()
+        ))
+        .expect("test server URL");
+
+        let mut path_responses =
+            std::collections::HashMap::<_, std::collections::VecDeque<_>>::new();
+        for (path, status, headers, body) in responses {
+            let header: String = headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                .collect();
+            path_responses
+                .entry(path.to_string())
+                .or_default()
+                .push_back((status, header, body));
+        }
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let server_requests = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                let request = match read_request(&mut stream) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let (status, header, body) = match path_responses.get_mut(&request.path) {
+                    Some(responses) if responses.len() > 1 => {
+                        responses.pop_front().expect("test server response")
+                    }
+                    Some(responses) => responses.front().cloned().expect("test server response"),
+                    None => (404, String::new(), b"{}".to_vec()),
+                };
+                server_requests
+                    .lock()
+                    .expect("test server requests lock")
+                    .push(request);
+
+                let header = format!(
+                    "HTTP/1.1 {} Test\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    header,
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).ok();
+                stream.write_all(&body).ok();
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Returns the requested paths, in request order.
+    pub fn requests(&self) -> Vec<String> {
+        self.get_requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect()
+    }
+
+    /// Returns the received requests, in request order.
+    pub fn get_requests(&self) -> Vec<Request> {
+        self.requests
+            .lock()
+            .expect("test server requests lock")
+            .clone()
+    }
+}
+
+/// Read a request head.
+fn read_request(stream: &mut std::net::TcpStream) -> Option<Request> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let length = stream.read(&mut buffer).ok()?;
+        if length == 0 {
+            return None;
+        }
+        head.extend_from_slice(&buffer[..length]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let path = lines.next()?.split_whitespace().nth(1)?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(Request { path, headers })
+}
+
+/// Returns an npm configuration given npmrc file content.
+///
+/// Packuments are cached in a temporary directory, rather than the user cache directory.
+pub fn get_npm_config(content: &str) -> crate::npmrc::Config {
+    static SET_CACHE_DIRECTORY: std::sync::Once = std::sync::Once::new();
+    SET_CACHE_DIRECTORY.call_once(|| {
+        let cache_directory =
+            std::env::temp_dir().join(format!("vouch_js_test_cache_{}", std::process::id()));
+        std::env::set_var("XDG_CACHE_HOME", cache_directory);
+    });
+    crate::npmrc::Config::from_content(content).expect("valid npm config")
+}
+
+/// Write a lockfile, and optionally a package.json, to a temporary project directory.
+///
+/// Returns the project directory, which is removed when dropped, and the lockfile path.
+pub fn write_lockfile(
+    file_name: &str,
+    lockfile: &str,
+    package_json: Option<&str>,
+) -> anyhow::Result<(tempdir::TempDir, std::path::PathBuf)> {
+    let project_directory = tempdir::TempDir::new("vouch_js_test_project")?;
+    let file_path = project_directory.path().join(file_name);
+    std::fs::write(&file_path, lockfile)?;
+    if let Some(package_json) = package_json {
+        std::fs::write(project_directory.path().join("package.json"), package_json)?;
+    }
+    Ok((project_directory, file_path))
+}
+
+/// Returns dependencies as `name@version` strings, in dependency order.
+pub fn get_names_versions(
+    dependencies: &std::collections::BTreeSet<vouch_lib::extension::Dependency>,
+) -> Vec<String> {
+    dependencies
+        .iter()
+        .map(|dependency| {
+            format!(
+                "{}@{}",
+                dependency.name,
+                dependency.version.clone().unwrap_or_default()
+            )
+        })
+        .collect()
+}
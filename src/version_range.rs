@@ -0,0 +1,455 @@
+use anyhow::{format_err, Result};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operator {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone)]
+struct Comparator {
+    operator: Operator,
+    version: semver::Version,
+}
+
+impl Comparator {
+    fn new(operator: Operator, version: semver::Version) -> Self {
+        Self { operator, version }
+    }
+
+    fn matches(&self, version: &semver::Version) -> bool {
+        match self.operator {
+            Operator::Lt => version < &self.version,
+            Operator::Le => version <= &self.version,
+            Operator::Gt => version > &self.version,
+            Operator::Ge => version >= &self.version,
+            Operator::Eq => version == &self.version,
+        }
+    }
+}
+
+/// Version with optional (wildcard) components. For example: `1.2` or `1.x`.
+#[derive(Debug, Clone)]
+struct Partial {
+    major: Option<u64>,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: semver::Prerelease,
+}
+
+impl Partial {
+    fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix('=').unwrap_or(value);
+        let value = value.strip_prefix('v').unwrap_or(value);
+        let value = value.split('+').next().unwrap_or_default();
+        let (release, pre) = match value.split_once('-') {
+            Some((release, pre)) => (release, semver::Prerelease::new(pre)?),
+            None => (value, semver::Prerelease::EMPTY),
+        };
+
+        let mut components = Vec::new();
+        for component in release.split('.') {
+            let component = match component {
+                "x" | "X" | "*" | "" => None,
+                _ => Some(
+                    component
+                        .parse::<u64>()
+                        .map_err(|_| format_err!("Invalid version component: {}", value))?,
+                ),
+            };
+            components.push(component);
+        }
+        if components.len() > 3 {
+            return Err(format_err!("Invalid version: {}", value));
+        }
+        components.resize(3, None);
+
+        // Components following a wildcard are also wildcards.
+        let major = components[0];
+        let minor = major.and(components[1]);
+        let patch = minor.and(components[2]);
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+
+    /// Lowest version matched by this partial.
+    fn floor(&self) -> semver::Version {
+        let mut version = semver::Version::new(
+            self.major.unwrap_or(0),
+            self.minor.unwrap_or(0),
+            self.patch.unwrap_or(0),
+        );
+        if self.patch.is_some() {
+            version.pre = self.pre.clone();
+        }
+        version
+    }
+
+    /// Exclusive upper bound of a partial which has wildcard components.
+    fn wildcard_ceiling(&self) -> Option<semver::Version> {
+        match (self.major, self.minor, self.patch) {
+            (Some(major), None, _) => Some(exclusive_bound(major + 1, 0, 0)),
+            (Some(major), Some(minor), None) => Some(exclusive_bound(major, minor + 1, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the lowest possible version preceding the given release, `x.y.z-0`.
+///
+/// Used as an exclusive upper bound so that prereleases of the release are excluded.
+fn exclusive_bound(major: u64, minor: u64, patch: u64) -> semver::Version {
+    let mut version = semver::Version::new(major, minor, patch);
+    version.pre = semver::Prerelease::new("0").expect("valid prerelease");
+    version
+}
+
+fn parse_tilde(partial: &Partial) -> Vec<Comparator> {
+    let ceiling = match (partial.major, partial.minor) {
+        (None, _) => return vec![],
+        (Some(major), None) => exclusive_bound(major + 1, 0, 0),
+        (Some(major), Some(minor)) => exclusive_bound(major, minor + 1, 0),
+    };
+    vec![
+        Comparator::new(Operator::Ge, partial.floor()),
+        Comparator::new(Operator::Lt, ceiling),
+    ]
+}
+
+fn parse_caret(partial: &Partial) -> Vec<Comparator> {
+    let ceiling = match (partial.major, partial.minor, partial.patch) {
+        (None, _, _) => return vec![],
+        (Some(0), Some(0), Some(patch)) => exclusive_bound(0, 0, patch + 1),
+        (Some(0), Some(minor), _) if minor > 0 || partial.patch.is_some() => {
+            exclusive_bound(0, minor + 1, 0)
+        }
+        (Some(0), Some(0), None) => exclusive_bound(0, 1, 0),
+        (Some(major), _, _) => exclusive_bound(major + 1, 0, 0),
+    };
+    vec![
+        Comparator::new(Operator::Ge, partial.floor()),
+        Comparator::new(Operator::Lt, ceiling),
+    ]
+}
+
+fn parse_primitive(operator: &str, partial: &Partial) -> Vec<Comparator> {
+    let is_wildcard = partial.patch.is_none();
+    match operator {
+        ">" => match (partial.major, partial.minor, partial.patch) {
+            // Nothing is greater than any version.
+            (None, _, _) => vec![Comparator::new(Operator::Lt, exclusive_bound(0, 0, 0))],
+            (Some(major), None, _) => vec![Comparator::new(
+                Operator::Ge,
+                semver::Version::new(major + 1, 0, 0),
+            )],
+            (Some(major), Some(minor), None) => vec![Comparator::new(
+                Operator::Ge,
+                semver::Version::new(major, minor + 1, 0),
+            )],
+            _ => vec![Comparator::new(Operator::Gt, partial.floor())],
+        },
+        ">=" => vec![Comparator::new(Operator::Ge, partial.floor())],
+        "<" => {
+            if partial.major.is_none() {
+                vec![Comparator::new(Operator::Lt, exclusive_bound(0, 0, 0))]
+            } else if is_wildcard {
+                vec![Comparator::new(Operator::Lt, exclusive_floor(partial))]
+            } else {
+                vec![Comparator::new(Operator::Lt, partial.floor())]
+            }
+        }
+        "<=" => match partial.wildcard_ceiling() {
+            Some(ceiling) => vec![Comparator::new(Operator::Lt, ceiling)],
+            None if partial.major.is_none() => vec![],
+            None => vec![Comparator::new(Operator::Le, partial.floor())],
+        },
+        _ => parse_x_range(partial),
+    }
+}
+
+/// Returns `x.y.z-0` for the floor of a wildcard partial.
+fn exclusive_floor(partial: &Partial) -> semver::Version {
+    let floor = partial.floor();
+    exclusive_bound(floor.major, floor.minor, floor.patch)
+}
+
+fn parse_x_range(partial: &Partial) -> Vec<Comparator> {
+    if partial.major.is_none() {
+        return vec![];
+    }
+    match partial.wildcard_ceiling() {
+        Some(ceiling) => vec![
+            Comparator::new(Operator::Ge, partial.floor()),
+            Comparator::new(Operator::Lt, ceiling),
+        ],
+        None => vec![Comparator::new(Operator::Eq, partial.floor())],
+    }
+}
+
+fn parse_hyphen(lower: &str, upper: &str) -> Result<Vec<Comparator>> {
+    let lower = Partial::parse(lower)?;
+    let upper = Partial::parse(upper)?;
+
+    let mut comparators = Vec::new();
+    if lower.major.is_some() {
+        comparators.push(Comparator::new(Operator::Ge, lower.floor()));
+    }
+    if upper.major.is_some() {
+        match upper.wildcard_ceiling() {
+            Some(ceiling) => comparators.push(Comparator::new(Operator::Lt, ceiling)),
+            None => comparators.push(Comparator::new(Operator::Le, upper.floor())),
+        }
+    }
+    Ok(comparators)
+}
+
+/// Split a comparator token into operator and version components.
+fn split_operator(token: &str) -> (&str, &str) {
+    for operator in &[">=", "<=", ">", "<", "=", "~>", "~", "^"] {
+        if let Some(version) = token.strip_prefix(operator) {
+            return (operator, version);
+        }
+    }
+    ("", token)
+}
+
+fn parse_comparator_set(value: &str) -> Result<Vec<Comparator>> {
+    let value = value.trim();
+    if let Some((lower, upper)) = value.split_once(" - ") {
+        return parse_hyphen(lower, upper);
+    }
+
+    // Join operators separated from their version by whitespace: `>= 1.2.3`.
+    let mut tokens: Vec<String> = Vec::new();
+    for token in value.split_whitespace() {
+        match tokens.last_mut() {
+            Some(last) if split_operator(last).1.is_empty() => last.push_str(token),
+            _ => tokens.push(token.to_string()),
+        }
+    }
+
+    let mut comparators = Vec::new();
+    for token in tokens {
+        let (operator, version) = split_operator(&token);
+        let partial = Partial::parse(version)?;
+        comparators.extend(match operator {
+            "~" | "~>" => parse_tilde(&partial),
+            "^" => parse_caret(&partial),
+            _ => parse_primitive(operator, &partial),
+        });
+    }
+    Ok(comparators)
+}
+
+/// npm version range. For example: `^1.2.3 || >=2.0.0 <3.0.0`.
+///
+/// Supports the range syntax accepted by npm: comparators (`>=1.2.3`), x-ranges
+/// (`1.2.x`, `*`), tilde (`~1.2.3`), caret (`^1.2.3`) and hyphen ranges
+/// (`1.2.3 - 2.3.4`), combined with whitespace (AND) and `||` (OR).
+#[derive(Debug, Clone)]
+pub struct Range {
+    comparator_sets: Vec<Vec<Comparator>>,
+}
+
+impl Range {
+    pub fn parse(range: &str) -> Result<Self> {
+        let comparator_sets = range
+            .split("||")
+            .map(parse_comparator_set)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|error| format_err!("Invalid version range {}: {}", range, error))?;
+        Ok(Self { comparator_sets })
+    }
+
+    /// Returns true if the version satisfies the range.
+    ///
+    /// Prerelease versions only satisfy a comparator set which explicitly includes a
+    /// prerelease of the same major, minor and patch version.
+    pub fn matches(&self, version: &semver::Version) -> bool {
+        // Build metadata does not affect matching.
+        let mut version = version.clone();
+        version.build = semver::BuildMetadata::EMPTY;
+        let version = &version;
+
+        self.comparator_sets.iter().any(|comparators| {
+            if !comparators.iter().all(|c| c.matches(version)) {
+                return false;
+            }
+            if version.pre.is_empty() {
+                return true;
+            }
+            comparators.iter().any(|c| {
+                !c.version.pre.is_empty()
+                    && c.version.major == version.major
+                    && c.version.minor == version.minor
+                    && c.version.patch == version.patch
+            })
+        })
+    }
+}
+
+/// Parse a version number, tolerating a leading `v` or `=`.
+pub fn parse_version(version: &str) -> Option<semver::Version> {
+    let version = version.trim();
+    let version = version.strip_prefix('=').unwrap_or(version);
+    let version = version.strip_prefix('v').unwrap_or(version);
+    semver::Version::parse(version).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matches(range: &str, matching: &[&str], not_matching: &[&str]) {
+        let parsed_range = Range::parse(range).unwrap();
+        for version in matching {
+            assert!(
+                parsed_range.matches(&semver::Version::parse(version).unwrap()),
+                "{} should match {}",
+                range,
+                version
+            );
+        }
+        for version in not_matching {
+            assert!(
+                !parsed_range.matches(&semver::Version::parse(version).unwrap()),
+                "{} should not match {}",
+                range,
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_caret_ranges() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            (
+                "^1.2.3",
+                &["1.2.3", "1.9.0"],
+                &["1.2.2", "2.0.0", "2.0.0-0"],
+            ),
+            ("^0.2.3", &["0.2.3", "0.2.9"], &["0.3.0", "0.2.2"]),
+            ("^0.0.3", &["0.0.3"], &["0.0.4", "0.0.2"]),
+            ("^1.2", &["1.2.0", "1.9.9"], &["1.1.9", "2.0.0"]),
+            ("^0.0", &["0.0.0", "0.0.9"], &["0.1.0"]),
+            ("^1.x", &["1.0.0", "1.9.9"], &["2.0.0", "0.9.9"]),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_tilde_ranges() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("~1.2.3", &["1.2.3", "1.2.9"], &["1.3.0", "1.2.2"]),
+            ("~1.2", &["1.2.0", "1.2.9"], &["1.3.0"]),
+            ("~1", &["1.0.0", "1.9.9"], &["2.0.0"]),
+            ("~0.2.3", &["0.2.3", "0.2.9"], &["0.3.0"]),
+            ("~>1.2.3", &["1.2.3", "1.2.9"], &["1.3.0"]),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_x_ranges() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("*", &["0.0.0", "1.2.3", "99.0.0"], &["1.0.0-beta.1"]),
+            ("", &["0.0.0", "1.2.3"], &[]),
+            ("1.x", &["1.0.0", "1.9.9"], &["2.0.0", "0.9.9"]),
+            ("1.2.x", &["1.2.0", "1.2.9"], &["1.3.0"]),
+            ("1.2.*", &["1.2.0", "1.2.9"], &["1.3.0"]),
+            ("1", &["1.0.0", "1.9.9"], &["2.0.0"]),
+            ("1.2.3", &["1.2.3"], &["1.2.4"]),
+            ("=v1.2.3", &["1.2.3"], &["1.2.4"]),
+            (">1.2", &["1.3.0"], &["1.2.9"]),
+            (">=1.2.3", &["1.2.3", "2.0.0"], &["1.2.2"]),
+            ("<1.2", &["1.1.9"], &["1.2.0", "1.2.0-0"]),
+            ("<=1.2", &["1.2.9"], &["1.3.0"]),
+            (">= 1.2.3 < 2", &["1.2.3", "1.9.9"], &["2.0.0"]),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_hyphen_ranges() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("1.2.3 - 2.3.4", &["1.2.3", "2.3.4"], &["1.2.2", "2.3.5"]),
+            ("1.2 - 2.3.4", &["1.2.0", "2.3.4"], &["1.1.9", "2.3.5"]),
+            ("1.2.3 - 2.3", &["1.2.3", "2.3.9"], &["2.4.0"]),
+            ("1.2.3 - 2", &["1.2.3", "2.9.9"], &["3.0.0"]),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_or_ranges() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("^1.2.3 || ^2.0.0", &["1.2.3", "2.5.0"], &["3.0.0", "1.2.2"]),
+            (
+                "<1.0.0 || >=2.0.0 <3.0.0",
+                &["0.9.0", "2.0.0"],
+                &["1.5.0", "3.0.0"],
+            ),
+            ("1.2.3 || 1.2.5", &["1.2.3", "1.2.5"], &["1.2.4"]),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_prerelease_exclusion() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("^1.2.3", &["1.2.4"], &["1.2.4-beta.1", "1.3.0-rc.1"]),
+            (
+                "^1.2.3-beta.2",
+                &["1.2.3-beta.2", "1.2.3-beta.10", "1.2.3", "1.3.0"],
+                &["1.2.3-beta.1", "1.2.4-beta.1", "1.2.3-alpha.9"],
+            ),
+            (">=1.0.0-rc.1 <2", &["1.0.0-rc.2", "1.5.0"], &["1.5.0-rc.1"]),
+            (
+                "1.2.3-beta.1",
+                &["1.2.3-beta.1"],
+                &["1.2.3-beta.2", "1.2.3"],
+            ),
+        ];
+        for (range, matching, not_matching) in cases {
+            assert_matches(range, matching, not_matching);
+        }
+    }
+
+    #[test]
+    fn test_build_metadata_ignored() {
+        assert_matches("^1.2.3", &["1.2.4+build.1"], &[]);
+        assert_matches("1.2.3+build.1", &["1.2.3"], &[]);
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        for range in &["^1.2.3.4", "1.a.3", ">=x.y.z-!"] {
+            assert!(Range::parse(range).is_err(), "{}", range);
+        }
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("v1.2.3"), Some(semver::Version::new(1, 2, 3)));
+        assert_eq!(parse_version("=1.2.3"), Some(semver::Version::new(1, 2, 3)));
+        assert_eq!(parse_version("1.2"), None);
+    }
+}
@@ -0,0 +1,757 @@
+use anyhow::{format_err, Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::{npm, package_json};
+
+/// Resolution protocol of packages fetched from the npm registry.
+static REGISTRY_PROTOCOL: &str = "npm";
+
+/// Lockfile entry common to the classic and Berry lockfile formats.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Descriptors which resolve to this entry.
+    specifiers: Vec<String>,
+    /// Descriptors of the dependencies required by this entry.
+    dependency_specifiers: Vec<String>,
+    package_name: String,
+    version: Option<String>,
+    /// Resolution protocol. For example: `npm`, `workspace` or `patch`.
+    protocol: String,
+}
+
+/// Yarn classic (v1) lockfile entry.
+///
+/// A single entry may be referenced by multiple specifiers, for example:
+/// `"lodash@^4.17.15", "lodash@^4.17.19":`.
+#[derive(Debug, Clone, Default)]
+struct ClassicEntry {
+    specifiers: Vec<String>,
+    /// Top level fields such as `version`, `resolved` and `integrity`.
+    fields: BTreeMap<String, String>,
+    /// Nested sections such as `dependencies` and `optionalDependencies`.
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Remove surrounding quotes from a lockfile token.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1].replace("\\\"", "\"")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split a `key value` line into its (unquoted) components.
+fn split_key_value(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    let key_end = if let Some(quoted_line) = line.strip_prefix('"') {
+        quoted_line.find('"')? + 2
+    } else {
+        line.find(' ')?
+    };
+    let (key, value) = line.split_at(key_end);
+    Some((unquote(key), unquote(value)))
+}
+
+/// Parse yarn classic lockfile content.
+fn parse_classic(content: &str) -> Result<Vec<ClassicEntry>> {
+    let mut entries: Vec<ClassicEntry> = Vec::new();
+    let mut section: Option<String> = None;
+
+    for (line_index, line) in content.lines().enumerate() {
+        let trimmed_line = line.trim();
+        if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let parse_error = || format_err!("Failed to parse yarn.lock line {}.", line_index + 1);
+
+        match indent {
+            0 => {
+                let header = trimmed_line.strip_suffix(':').ok_or_else(parse_error)?;
+                entries.push(ClassicEntry {
+                    specifiers: header.split(", ").map(unquote).collect(),
+                    ..Default::default()
+                });
+                section = None;
+            }
+            2 => {
+                let entry = entries.last_mut().ok_or_else(parse_error)?;
+                if let Some(section_name) = trimmed_line.strip_suffix(':') {
+                    let section_name = unquote(section_name);
+                    entry.sections.insert(section_name.clone(), BTreeMap::new());
+                    section = Some(section_name);
+                } else {
+                    let (key, value) = split_key_value(trimmed_line).ok_or_else(parse_error)?;
+                    entry.fields.insert(key, value);
+                    section = None;
+                }
+            }
+            4 => {
+                let entry = entries.last_mut().ok_or_else(parse_error)?;
+                let section_name = section.as_ref().ok_or_else(parse_error)?;
+                let (key, value) = split_key_value(trimmed_line).ok_or_else(parse_error)?;
+                entry
+                    .sections
+                    .get_mut(section_name)
+                    .ok_or_else(parse_error)?
+                    .insert(key, value);
+            }
+            _ => return Err(parse_error()),
+        }
+    }
+    Ok(entries)
+}
+
+/// Derive registry package name from a lockfile specifier.
+///
+/// Aliased specifiers (`alias@npm:name@range`) resolve to the aliased package name.
+fn get_package_name(specifier: &str) -> Option<String> {
+    let (name, range) = npm::split_specifier(specifier)?;
+    if let Some(aliased_specifier) = range.strip_prefix("npm:") {
+        return match npm::split_specifier(aliased_specifier) {
+            Some((aliased_name, _)) => Some(aliased_name.to_string()),
+            None => Some(aliased_specifier.to_string()),
+        };
+    }
+    Some(name.to_string())
+}
+
+/// Returns the resolution protocol of a classic lockfile entry.
+///
+/// Classic lockfiles do not record a protocol. It is derived from a `resolved` value which
+/// is not an archive URL (`git+ssh://...` yields `git`), or otherwise from the header range
+/// (`github:owner/repo` yields `github`). Archive URL ranges yield `tarball`.
+fn get_classic_protocol(classic_entry: &ClassicEntry) -> String {
+    let get_scheme = |reference: &str| -> Option<String> {
+        let (scheme, _) = reference.split_once(':')?;
+        Some(scheme.split('+').next().unwrap_or(scheme).to_string())
+    };
+
+    if let Some(scheme) = classic_entry
+        .fields
+        .get("resolved")
+        .and_then(|resolved| get_scheme(resolved))
+    {
+        if scheme != "http" && scheme != "https" {
+            return scheme;
+        }
+    }
+    let range = classic_entry
+        .specifiers
+        .first()
+        .and_then(|specifier| npm::split_specifier(specifier))
+        .map(|(_, range)| range)
+        .unwrap_or_default();
+    match get_scheme(range).as_deref() {
+        None | Some("npm") => REGISTRY_PROTOCOL.to_string(),
+        Some("http") | Some("https") => "tarball".to_string(),
+        Some(scheme) => scheme.to_string(),
+    }
+}
+
+/// Convert classic lockfile entries into common entries.
+fn get_classic_entries(content: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for classic_entry in parse_classic(content)? {
+        let package_name = match classic_entry
+            .specifiers
+            .first()
+            .and_then(|s| get_package_name(s))
+        {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let mut dependency_specifiers = Vec::new();
+        for section in &["dependencies", "optionalDependencies"] {
+            if let Some(dependencies) = classic_entry.sections.get(*section) {
+                for (name, range) in dependencies {
+                    dependency_specifiers.push(format!("{}@{}", name, range));
+                }
+            }
+        }
+
+        entries.push(Entry {
+            version: classic_entry.fields.get("version").cloned(),
+            protocol: get_classic_protocol(&classic_entry),
+            specifiers: classic_entry.specifiers,
+            dependency_specifiers,
+            package_name,
+        });
+    }
+    Ok(entries)
+}
+
+/// Returns a Berry descriptor given a dependency name and range.
+///
+/// Berry descriptors always include a protocol. Plain semver ranges use the npm protocol.
+fn get_berry_specifier(name: &str, range: &str) -> String {
+    if range.contains(':') {
+        format!("{}@{}", name, range)
+    } else {
+        format!("{}@{}:{}", name, REGISTRY_PROTOCOL, range)
+    }
+}
+
+/// Returns the descriptor targeted by a Berry patch descriptor.
+///
+/// Example: `typescript@patch:typescript@npm%3A^4.0.0#~builtin<compat/typescript>`
+/// yields `typescript@npm:^4.0.0`.
+fn get_patched_specifier(specifier: &str) -> Option<String> {
+    let (_, reference) = npm::split_specifier(specifier)?;
+    let patched_specifier = reference.strip_prefix("patch:")?.split('#').next()?;
+    let patched_specifier = patched_specifier.replace("%3A", ":");
+    let (name, range) = npm::split_specifier(&patched_specifier)?;
+    Some(get_berry_specifier(name, range))
+}
+
+/// Convert a scalar YAML value into a string.
+pub fn yaml_to_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(v) => Some(v.clone()),
+        serde_yaml::Value::Number(v) => Some(v.to_string()),
+        serde_yaml::Value::Bool(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// Parse Yarn Berry (v2+) lockfile content into common entries.
+fn get_berry_entries(content: &str) -> Result<Vec<Entry>> {
+    let lockfile: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(content)?;
+
+    let mut entries = Vec::new();
+    for (key, entry) in &lockfile {
+        if key == "__metadata" {
+            continue;
+        }
+
+        let resolution = entry["resolution"].as_str().ok_or(format_err!(
+            "Failed to find resolution field for entry: {}",
+            key
+        ))?;
+        let (package_name, reference) = npm::split_specifier(resolution)
+            .ok_or(format_err!("Failed to parse resolution: {}", resolution))?;
+        let protocol = reference.split(':').next().unwrap_or_default();
+
+        // The root workspace is the project itself rather than a dependency.
+        if protocol == "workspace" && reference == "workspace:." {
+            continue;
+        }
+
+        let mut dependency_specifiers = Vec::new();
+        if let Some(dependencies) = entry["dependencies"].as_mapping() {
+            for (name, range) in dependencies {
+                if let (Some(name), Some(range)) = (yaml_to_string(name), yaml_to_string(range)) {
+                    dependency_specifiers.push(get_berry_specifier(&name, &range));
+                }
+            }
+        }
+
+        let mut specifiers: Vec<_> = key.split(", ").map(|s| s.trim().to_string()).collect();
+        if protocol == "patch" {
+            // Patched packages should be reachable from the descriptor they patch.
+            let patched_specifiers: Vec<_> = specifiers
+                .iter()
+                .filter_map(|s| get_patched_specifier(s))
+                .collect();
+            specifiers.extend(patched_specifiers);
+        }
+
+        entries.push(Entry {
+            specifiers,
+            dependency_specifiers,
+            package_name: package_name.to_string(),
+            version: yaml_to_string(&entry["version"]),
+            protocol: protocol.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Returns true if lockfile content is in the Yarn Berry (v2+) format.
+///
+/// Berry lockfiles start with a `__metadata` section. Classic lockfiles do not.
+fn is_berry_lockfile(content: &str) -> bool {
+    content.lines().any(|line| line.starts_with("__metadata:"))
+}
+
+/// Returns non-dev root dependency names and ranges from the project's package.json file.
+///
+/// Returns None if the file is absent or defines workspaces, in which case
+/// dependency reachability can not be determined from this file alone.
+fn get_root_dependencies(
+    package_json_path: &std::path::PathBuf,
+) -> Result<Option<Vec<(String, String)>>> {
+    if !package_json_path.is_file() {
+        return Ok(None);
+    }
+    let package_json = package_json::read(package_json_path)?;
+    if !package_json["workspaces"].is_null() {
+        return Ok(None);
+    }
+
+    let mut root_dependencies = Vec::new();
+    for section in &["dependencies", "optionalDependencies"] {
+        if let Some(dependencies) = package_json[section].as_object() {
+            for (name, range) in dependencies {
+                let range = range.as_str().unwrap_or_default().to_string();
+                root_dependencies.push((name.clone(), range));
+            }
+        }
+    }
+    Ok(Some(root_dependencies))
+}
+
+/// Returns entries reachable from the given root specifiers.
+fn get_reachable_entries<'a>(entries: &'a [Entry], root_specifiers: &[String]) -> Vec<&'a Entry> {
+    let mut specifier_index: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        for specifier in &entry.specifiers {
+            specifier_index
+                .entry(specifier.as_str())
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut unprocessed_specifiers: VecDeque<&str> =
+        root_specifiers.iter().map(|s| s.as_str()).collect();
+    while let Some(specifier) = unprocessed_specifiers.pop_front() {
+        let indexes = match specifier_index.get(specifier) {
+            Some(indexes) => indexes,
+            None => continue,
+        };
+        for index in indexes {
+            if !visited.insert(*index) {
+                continue;
+            }
+            for dependency_specifier in &entries[*index].dependency_specifiers {
+                unprocessed_specifiers.push_back(dependency_specifier);
+            }
+        }
+    }
+
+    let mut visited: Vec<_> = visited.into_iter().collect();
+    visited.sort();
+    visited.into_iter().map(|index| &entries[index]).collect()
+}
+
+/// Parse dependencies from a yarn.lock file.
+///
+/// Packages resolved through protocols other than the npm registry (`workspace:`,
+/// `patch:`, `portal:`, `link:`, ...) are excluded by protocol name.
+///
+/// yarn.lock does not record dev dependency flags. Dev dependencies are therefore
+/// excluded by walking the dependency graph from the non-dev dependencies
+/// declared in the neighbouring package.json file, where possible.
+pub fn get_dependencies(
+    file_path: &std::path::PathBuf,
+    include_dev_dependencies: bool,
+) -> Result<npm::FileDependencies> {
+    let content = std::fs::read_to_string(file_path)?;
+    let is_berry = is_berry_lockfile(&content);
+    let entries = if is_berry {
+        get_berry_entries(&content)
+    } else {
+        get_classic_entries(&content)
+    }
+    .context(format!(
+        "Failed to parse yarn.lock: {}",
+        file_path.display()
+    ))?;
+
+    let root_dependencies = if include_dev_dependencies {
+        None
+    } else {
+        let package_json_path = file_path.with_file_name("package.json");
+        get_root_dependencies(&package_json_path)?
+    };
+    let entries = match root_dependencies {
+        Some(root_dependencies) => {
+            let root_specifiers: Vec<_> = root_dependencies
+                .iter()
+                .map(|(name, range)| {
+                    if is_berry {
+                        get_berry_specifier(name, range)
+                    } else {
+                        format!("{}@{}", name, range)
+                    }
+                })
+                .collect();
+            get_reachable_entries(&entries, &root_specifiers)
+        }
+        None => entries.iter().collect(),
+    };
+
+    let mut all_dependencies = npm::FileDependencies::default();
+    for entry in entries {
+        let protocol = if entry.protocol == REGISTRY_PROTOCOL {
+            None
+        } else {
+            Some(entry.protocol.clone())
+        };
+        all_dependencies.insert(
+            protocol,
+            vouch_lib::extension::Dependency {
+                name: entry.package_name.clone(),
+                version: npm::get_parsed_version(&entry.version.as_deref()),
+            },
+        );
+    }
+    Ok(all_dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, get_names_versions};
+
+    static CLASSIC_LOCKFILE: &str = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.10.4":
+  version "7.12.13"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.12.13.tgz#dcfc826beef65e75c50e21d3837d7d95798dd658"
+  integrity sha512-HV1Cm0Q3ZrpCR93tkWOYiuYIgLxZXZFVG2VgK+MBWjUqZTundupbfx2aXarXuw5Ko5aMcjtJgbSs4vUGBS5v6g==
+  dependencies:
+    "@babel/highlight" "^7.12.13"
+
+"@babel/highlight@^7.12.13":
+  version "7.13.10"
+  dependencies:
+    js-tokens "^4.0.0"
+
+js-tokens@^4.0.0:
+  version "4.0.0"
+
+lodash@^4.17.15, lodash@^4.17.19:
+  version "4.17.21"
+  optionalDependencies:
+    fsevents "~2.3.1"
+
+fsevents@~2.3.1:
+  version "2.3.2"
+
+jest@^26.6.0:
+  version "26.6.3"
+  dependencies:
+    lodash "^4.17.19"
+"#;
+
+    static BERRY_LOCKFILE: &str = r#"# This file is generated by running "yarn install" inside your project.
+
+__metadata:
+  version: 6
+  cacheKey: 8
+
+"my-project@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "my-project@workspace:."
+  dependencies:
+    "@scope/utils": "workspace:packages/utils"
+    lodash: ^4.17.21
+    typescript: "patch:typescript@^4.5.2#~builtin<compat/typescript>"
+  languageName: unknown
+  linkType: soft
+
+"@scope/utils@workspace:packages/utils":
+  version: 0.0.0-use.local
+  resolution: "@scope/utils@workspace:packages/utils"
+  dependencies:
+    is-odd: ^3.0.1
+  languageName: unknown
+  linkType: soft
+
+"is-odd@npm:^3.0.1":
+  version: 3.0.1
+  resolution: "is-odd@npm:3.0.1"
+  languageName: node
+  linkType: hard
+
+"lodash@npm:^4.17.20, lodash@npm:^4.17.21":
+  version: 4.17.21
+  resolution: "lodash@npm:4.17.21"
+  languageName: node
+  linkType: hard
+
+"typescript@npm:^4.5.2":
+  version: 4.5.4
+  resolution: "typescript@npm:4.5.4"
+  languageName: node
+  linkType: hard
+
+"typescript@patch:typescript@^4.5.2#~builtin<compat/typescript>":
+  version: 4.5.4
+  resolution: "typescript@patch:typescript@npm%3A4.5.4#~builtin<compat/typescript>::version=4.5.4&hash=493e53"
+  languageName: node
+  linkType: hard
+
+"jest@npm:^27.0.0":
+  version: 27.4.5
+  resolution: "jest@npm:27.4.5"
+  languageName: node
+  linkType: hard
+"#;
+
+    #[test]
+    fn test_parse_classic_headers() -> Result<()> {
+        let entries = parse_classic(CLASSIC_LOCKFILE)?;
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[0].specifiers,
+            vec!["@babel/code-frame@^7.0.0", "@babel/code-frame@^7.10.4"]
+        );
+        assert_eq!(
+            entries[3].specifiers,
+            vec!["lodash@^4.17.15", "lodash@^4.17.19"]
+        );
+        assert_eq!(entries[0].fields["version"], "7.12.13");
+        assert!(
+            entries[0].fields["resolved"].ends_with("#dcfc826beef65e75c50e21d3837d7d95798dd658")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_classic_sections() -> Result<()> {
+        let entries = parse_classic(CLASSIC_LOCKFILE)?;
+        assert_eq!(
+            entries[0].sections["dependencies"]["@babel/highlight"],
+            "^7.12.13"
+        );
+        assert_eq!(
+            entries[3].sections["optionalDependencies"]["fsevents"],
+            "~2.3.1"
+        );
+        assert!(!entries[3].sections.contains_key("dependencies"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_classic_rejects_unexpected_indentation() {
+        let content = "lodash@^4.17.21:\n      version \"4.17.21\"\n";
+        assert!(parse_classic(content).is_err());
+    }
+
+    #[test]
+    fn test_get_classic_entries_dependency_specifiers() -> Result<()> {
+        let entries = get_classic_entries(CLASSIC_LOCKFILE)?;
+        assert_eq!(entries[0].package_name, "@babel/code-frame");
+        assert_eq!(
+            entries[0].dependency_specifiers,
+            vec!["@babel/highlight@^7.12.13"]
+        );
+        assert_eq!(entries[3].dependency_specifiers, vec!["fsevents@~2.3.1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_package_name_alias() {
+        assert_eq!(
+            get_package_name("string-width-cjs@npm:string-width@^4.2.0"),
+            Some("string-width".to_string())
+        );
+        assert_eq!(
+            get_package_name("@babel/core@^7.0.0"),
+            Some("@babel/core".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_dependencies_excludes_dev_dependencies() -> Result<()> {
+        let (_project_directory, file_path) = testing::write_lockfile(
+            "yarn.lock",
+            CLASSIC_LOCKFILE,
+            Some(
+                r#"{
+                    "dependencies": {"@babel/code-frame": "^7.10.4"},
+                    "optionalDependencies": {"lodash": "^4.17.15"},
+                    "devDependencies": {"jest": "^26.6.0"}
+                }"#,
+            ),
+        )?;
+
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            vec![
+                "@babel/code-frame@7.12.13",
+                "@babel/highlight@7.13.10",
+                "fsevents@2.3.2",
+                "js-tokens@4.0.0",
+                "lodash@4.17.21",
+            ]
+        );
+
+        let dependencies = get_dependencies(&file_path, true)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies).len(),
+            6
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_berry_entries_skips_root_workspace() -> Result<()> {
+        let entries = get_berry_entries(BERRY_LOCKFILE)?;
+        assert!(entries
+            .iter()
+            .all(|entry| entry.package_name != "my-project"));
+        let utils_entry = entries
+            .iter()
+            .find(|entry| entry.package_name == "@scope/utils")
+            .unwrap();
+        assert_eq!(utils_entry.protocol, "workspace");
+        assert_eq!(utils_entry.dependency_specifiers, vec!["is-odd@npm:^3.0.1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_berry_entries_patch_specifiers() -> Result<()> {
+        let entries = get_berry_entries(BERRY_LOCKFILE)?;
+        let patch_entry = entries
+            .iter()
+            .find(|entry| entry.protocol == "patch")
+            .unwrap();
+        assert_eq!(patch_entry.package_name, "typescript");
+        assert_eq!(
+            patch_entry.specifiers,
+            vec![
+                "typescript@patch:typescript@^4.5.2#~builtin<compat/typescript>",
+                "typescript@npm:^4.5.2"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_patched_specifier() {
+        assert_eq!(
+            get_patched_specifier(
+                "typescript@patch:typescript@npm%3A^4.0.0#~builtin<compat/typescript>"
+            ),
+            Some("typescript@npm:^4.0.0".to_string())
+        );
+        assert_eq!(get_patched_specifier("lodash@npm:^4.17.21"), None);
+    }
+
+    #[test]
+    fn test_get_dependencies_berry() -> Result<()> {
+        let (_project_directory, file_path) = testing::write_lockfile(
+            "yarn.lock",
+            BERRY_LOCKFILE,
+            Some(
+                r#"{
+                    "dependencies": {
+                        "@scope/utils": "workspace:packages/utils",
+                        "lodash": "^4.17.21",
+                        "typescript": "^4.5.2"
+                    },
+                    "devDependencies": {"jest": "^27.0.0"}
+                }"#,
+            ),
+        )?;
+
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            vec!["is-odd@3.0.1", "lodash@4.17.21", "typescript@4.5.4"]
+        );
+        assert_eq!(
+            dependencies
+                .excluded_dependencies
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["patch", "workspace"]
+        );
+        assert_eq!(
+            get_names_versions(&dependencies.excluded_dependencies["workspace"]),
+            vec!["@scope/utils@0.0.0-use.local"]
+        );
+
+        let dependencies = get_dependencies(&file_path, true)?;
+        assert!(get_names_versions(&dependencies.registry_dependencies)
+            .contains(&"jest@27.4.5".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_dependencies_without_package_json() -> Result<()> {
+        let (_project_directory, file_path) =
+            testing::write_lockfile("yarn.lock", CLASSIC_LOCKFILE, None)?;
+
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies).len(),
+            6
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_dependencies_classic_non_registry_protocols() -> Result<()> {
+        let (_project_directory, file_path) = testing::write_lockfile(
+            "yarn.lock",
+            r#"# yarn lockfile v1
+
+
+"left-pad@^1.3.0":
+  version "1.3.0"
+  resolved "https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz#5b8a3a7765dfe001261dde915589e782f8c94d1e"
+
+"my-lib@github:owner/my-lib":
+  version "1.0.0"
+  resolved "git+ssh://git@github.com/owner/my-lib.git#0123456789abcdef0123456789abcdef01234567"
+
+"other-lib@owner/other-lib":
+  version "1.1.0"
+  resolved "git+https://github.com/owner/other-lib.git#0123456789abcdef0123456789abcdef01234567"
+
+"local@file:../local":
+  version "2.0.0"
+
+"linked@link:../linked":
+  version "0.0.0"
+
+"archive@https://example.com/archive-3.0.0.tgz":
+  version "3.0.0"
+  resolved "https://example.com/archive-3.0.0.tgz"
+
+"string-width-cjs@npm:string-width@^4.2.0":
+  version "4.2.3"
+  resolved "https://registry.yarnpkg.com/string-width/-/string-width-4.2.3.tgz"
+"#,
+            None,
+        )?;
+
+        let dependencies = get_dependencies(&file_path, false)?;
+        assert_eq!(
+            get_names_versions(&dependencies.registry_dependencies),
+            vec!["left-pad@1.3.0", "string-width@4.2.3"]
+        );
+        assert_eq!(
+            dependencies
+                .excluded_dependencies
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["file", "git", "link", "tarball"]
+        );
+        let cases = vec![
+            ("file", vec!["local@2.0.0"]),
+            ("git", vec!["my-lib@1.0.0", "other-lib@1.1.0"]),
+            ("link", vec!["linked@0.0.0"]),
+            ("tarball", vec!["archive@3.0.0"]),
+        ];
+        for (protocol, expected) in cases {
+            assert_eq!(
+                get_names_versions(&dependencies.excluded_dependencies[protocol]),
+                expected
+            );
+        }
+        Ok(())
+    }
+}